@@ -15,7 +15,9 @@ fn List(cx: Scope) {
             nums.clear()
         }>"clear!"</button>
         <div>
-            "nums={nums:?}"
+            <for num in {nums.iter()}>
+                <div>"{num}"</div>
+            </for>
         </div>
     }
 }
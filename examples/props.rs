@@ -9,13 +9,18 @@ fn TakesProp(cx: Scope, number: i32) {
     }
 }
 
+#[component]
+fn App(cx: Scope) {
+    rsx! {
+        <div>
+            // The component takes a prop called `number` of type `i32`.
+            <TakesProp number=42 />
+            <TakesProp number={6 * 7} />
+        </div>
+    }
+}
+
 fn main() {
     let ui = WebRenderer::default();
-    launch(
-        ui,
-        TakesProp {
-            // The component takes a prop called `number` of type `i32`.
-            number: 42,
-        },
-    );
+    launch(ui, App {});
 }
@@ -9,6 +9,7 @@ use crate::component_visitor::ComponentBuilder;
 use crate::component_visitor_mut::ComponentVisitorMut;
 use crate::memo::Memo;
 use crate::prop::Prop;
+use crate::rsx::combine;
 use crate::rsx::Elements;
 use crate::state::State;
 
@@ -17,6 +18,8 @@ pub struct Component {
     pub type_name: Ident,
     pub states: Vec<State>,
     pub memos: Vec<Memo>,
+    // the ids of the memos, each after the memos it reads
+    pub memo_order: Vec<usize>,
     pub rsx: Elements,
     pub fn_item: ItemFn,
     pub prop_items: Vec<Prop>,
@@ -46,6 +49,83 @@ impl Component {
         }
     }
 
+    fn regions(&self) -> impl Iterator<Item = Ident> + '_ {
+        self.rsx.roots.iter().flat_map(|root| {
+            root.dynamic_nodes
+                .iter()
+                .filter_map(|dyn_node| dyn_node.region_ident())
+        })
+    }
+
+    fn children(&self) -> impl Iterator<Item = Ident> + '_ {
+        self.rsx.roots.iter().flat_map(|root| {
+            root.dynamic_nodes
+                .iter()
+                .filter_map(|dyn_node| dyn_node.child_ident())
+        })
+    }
+
+    /// The set `DirtyTrackSet` uses: the smallest integer with a bit for every state and memo, or a multi-word set if there are more than 128.
+    pub fn tracking_ty(&self) -> TokenStream {
+        let bits = self.states.len() + self.memos.len();
+        match bits {
+            0..=8 => quote!(u8),
+            9..=16 => quote!(u16),
+            17..=32 => quote!(u32),
+            33..=64 => quote!(u64),
+            65..=128 => quote!(u128),
+            _ => {
+                let words = bits.div_ceil(64);
+                quote!(BitSet<#words>)
+            }
+        }
+    }
+
+    /// The bit of a memo in the tracking set, after the bits of the states.
+    pub fn memo_bit(&self, memo_id: usize) -> usize {
+        self.states.len() + memo_id
+    }
+
+    /// Rerun the memos in a set of dirty memos in topological order, so each memo runs once and sees the new values of the memos it reads. The memos that read a memo only rerun if its value changed.
+    ///
+    /// Memos that write state can dirty memos earlier in the order, those run in another pass.
+    fn run_memos(&self) -> TokenStream {
+        let tracking = self.tracking_ty();
+        let memos = self
+            .memo_order
+            .iter()
+            .map(|id| &self.memos[*id])
+            .filter(|memo| !memo.runs_once())
+            .map(|memo| {
+                let bit = self.memo_bit(memo.id);
+                let update_fn = memo.update_fn();
+                let subscribers = memo.subscribers.iter().map(|id| self.memo_bit(*id));
+                // the memos it created were replaced by ones that already ran
+                let children = memo.children.iter().map(|id| self.memo_bit(*id));
+                quote! {
+                    if qk::prelude::Bits::contains(&dirty, #bit) {
+                        let (changed, written) = self.#update_fn();
+                        #(dirty = qk::prelude::Bits::without(dirty, #children);)*
+                        next = qk::prelude::Bits::union(next, written);
+                        if changed {
+                            #(dirty = qk::prelude::Bits::union(dirty, qk::prelude::Bits::bit(#subscribers));)*
+                        }
+                    }
+                }
+            });
+
+        quote! {
+            #[allow(unused_mut, unused_variables)]
+            fn run_memos(&mut self, mut dirty: #tracking) {
+                while !qk::prelude::Bits::is_empty(&dirty) {
+                    let mut next: #tracking = Default::default();
+                    #(#memos)*
+                    dirty = next;
+                }
+            }
+        }
+    }
+
     fn comp_name(&self) -> Ident {
         Ident::new(&format!("{}State", self.type_name), self.type_name.span())
     }
@@ -55,11 +135,12 @@ impl ToTokens for Component {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let body = &self.fn_item.block.stmts;
 
+        let tracking = self.tracking_ty();
         let update_states = self
             .states
             .iter()
             .map(|state| {
-                let update = state.update();
+                let update = state.update(self);
                 quote! {
                     #update
                 }
@@ -73,6 +154,7 @@ impl ToTokens for Component {
                 })
             }));
 
+        let run_memos = self.run_memos();
         let comp_name = self.comp_name();
         let types = self
             .states
@@ -87,6 +169,16 @@ impl ToTokens for Component {
                 root.dynamic_nodes
                     .iter()
                     .map(|dyn_node| dyn_node.type_def())
+            }))
+            .chain(self.regions().map(|region| {
+                quote! {
+                    #region: std::rc::Rc<std::cell::RefCell<qk::fragment::Region>>
+                }
+            }))
+            .chain(self.children().map(|child| {
+                quote! {
+                    #child: qk::component::DynComponentState<R, R>
+                }
             }));
 
         let create_comp = self
@@ -117,6 +209,11 @@ impl ToTokens for Component {
                         #name
                     }
                 })
+            }))
+            .chain(self.regions().chain(self.children()).map(|field| {
+                quote! {
+                    #field
+                }
             }));
 
         let ident_init = self
@@ -150,20 +247,45 @@ impl ToTokens for Component {
                         let mut #name: u32 = 0;
                     }
                 })
+            }))
+            .chain(self.regions().map(|region| {
+                quote! {
+                    let #region: std::rc::Rc<std::cell::RefCell<qk::fragment::Region>>;
+                }
+            }))
+            .chain(self.children().map(|child| {
+                quote! {
+                    let #child: qk::component::DynComponentState<R, R>;
+                }
             }));
 
-        let roots = self.rsx.roots.iter().map(|root| {
-            let name = root.root_ident();
-            quote! {
-                #name
-            }
-        });
+        // Blocks created by regions add listeners before the component exists, so they get it from a handle that is filled in afterwards
+        let has_regions = self.rsx.has_regions();
+        let (create_handle, set_handle) = if has_regions {
+            (
+                quote! {
+                    let __comp_handle: std::rc::Rc<std::cell::RefCell<Option<std::rc::Rc<std::cell::RefCell<#comp_name<R>>>>>> = Default::default();
+                },
+                quote! {
+                    *__comp_handle.borrow_mut() = Some(comp.clone());
+                },
+            )
+        } else {
+            Default::default()
+        };
+
+        let roots_ident = Ident::new("roots", proc_macro2::Span::call_site());
+        let roots = self
+            .rsx
+            .roots
+            .iter()
+            .map(|root| root.push_roots(&quote!(self.), &roots_ident));
 
         let listeners = self.rsx.roots.iter().map(|root|{
             let dynamic_nodes = &root.dynamic_nodes;
 
             let listeners = dynamic_nodes.iter().filter_map(|dyn_node|{
-                dyn_node.listeners(&self.states, &self.comp_name())
+                dyn_node.listeners(&self.states, &self.comp_name(), None, &[])
             }).map(|listener|{
                 quote! {
                     #listener
@@ -189,12 +311,13 @@ impl ToTokens for Component {
             #props_struct
 
             struct #comp_name<R: qk::renderer::Renderer<R> + qk::events::PlatformEvents> {
-                tracking: DirtyTrackSet<u8, u8>,
+                tracking: DirtyTrackSet<#tracking, #tracking>,
                 ui: R,
                 #(#types,)*
             }
             impl<R: qk::renderer::Renderer<R> + qk::events::PlatformEvents> #comp_name<R> {
                 #(#update_states)*
+                #run_memos
             }
 
             impl<R: qk::renderer::Renderer<R> + qk::events::PlatformEvents + Clone + 'static> qk::component::Component<R, R> for #prop_name {
@@ -202,7 +325,9 @@ impl ToTokens for Component {
                 
                 fn create(self, ui: &mut R) -> Self::State {
                     let Self { #(#props,)* } = self;
-                    let tracking: DirtyTrackSet<u8, u8> = DirtyTrackSet::default();
+                    ui.start_component();
+                    let tracking: DirtyTrackSet<#tracking, #tracking> = DirtyTrackSet::default();
+                    #create_handle
                     #(#ident_init)*
                     #(#body)*
                     let mut comp = #comp_name {
@@ -212,16 +337,26 @@ impl ToTokens for Component {
                     };
 
                     let comp = std::rc::Rc::new(std::cell::RefCell::new(comp));
+                    #set_handle
 
                     #(#listeners)*
 
+                    ui.end_component();
                     comp
                 }
             }
 
+            impl<R: qk::renderer::Renderer<R> + qk::events::PlatformEvents + Clone + 'static> qk::content::Content<R, R> for #prop_name {
+                fn pieces(self, pieces: &mut Vec<qk::content::Piece<R, R>>) {
+                    pieces.push(qk::content::Piece::component(self));
+                }
+            }
+
             impl<R: qk::renderer::Renderer<R> + qk::events::PlatformEvents> qk::component::ComponentState<R, R> for #comp_name<R> {
                 fn roots(&self) -> Vec<u32> {
-                    vec![#(self.#roots,)*]
+                    let mut roots = Vec::new();
+                    #(#roots)*
+                    roots
                 }
             }
         })
@@ -240,18 +375,22 @@ impl Parse for Component {
             fn_item: f.clone(),
             type_name,
             in_reactive: false,
+            owner: None,
+            errors: Vec::new(),
         };
 
         visitor.visit_item_fn(&f);
 
         let mut myself = visitor.build()?;
 
-        ComponentVisitorMut {
+        let mut visitor = ComponentVisitorMut {
             component: &myself,
             memo_idx: 0,
             state_idx: 0,
-        }
-        .visit_item_fn_mut(&mut f);
+            errors: Vec::new(),
+        };
+        visitor.visit_item_fn_mut(&mut f);
+        combine(visitor.errors)?;
 
         myself.fn_item = f;
 
@@ -1,6 +1,7 @@
 use crate::component::Component;
-use crate::memo::Memo;
-use crate::rsx::Elements;
+use crate::memo::{update_order, Memo};
+use crate::prop::Prop;
+use crate::rsx::{combine, Elements};
 use crate::state::State;
 use proc_macro2::Ident;
 use quote::ToTokens;
@@ -17,16 +18,31 @@ pub struct ComponentBuilder {
     pub fn_item: ItemFn,
     pub type_name: Ident,
     pub in_reactive: bool,
+    // the memo whose closure is being visited, memos created in it belong to it
+    pub owner: Option<usize>,
+    // unsupported syntax in the component, reported together with the errors of the rsx
+    pub errors: Vec<syn::Error>,
 }
 
 impl ComponentBuilder {
     pub fn state(&mut self, name: Ident, ty: Type, expr: Expr) {
+        // the initial value can be computed from the states and memos before it
+        let mut visitor = SubscriptionVisitor {
+            states: &self.states,
+            memos: &self.memos,
+            subscribed: Default::default(),
+            memos_read: Default::default(),
+        };
+        visitor.visit_expr(&expr);
+
         self.states.push(State {
             id: self.states.len(),
             name,
             ty,
             expr,
             subscribers: Default::default(),
+            init_states: visitor.subscribed,
+            init_memos: visitor.memos_read,
         })
     }
 
@@ -44,7 +60,9 @@ impl ComponentBuilder {
 
         let mut visitor = SubscriptionVisitor {
             states: &self.states,
+            memos: &self.memos,
             subscribed: Default::default(),
+            memos_read: Default::default(),
         };
         visitor.visit_expr(&closure);
 
@@ -55,8 +73,13 @@ impl ComponentBuilder {
             closure: Some(closure),
             capture,
             subscriptions: visitor.subscribed.into_iter().collect(),
+            memo_subscriptions: visitor.memos_read.into_iter().collect(),
             subscribers: Default::default(),
             raw_params,
+            compare: true,
+            name: None,
+            owner: None,
+            children: Vec::new(),
         });
 
         id
@@ -69,21 +92,34 @@ impl ComponentBuilder {
             rsx,
             fn_item,
             type_name,
+            mut errors,
             ..
         } = self;
-        let rsx = rsx.expect("rsx macro is required")?;
+        let rsx = rsx.unwrap_or_else(|| {
+            Err(syn::Error::new_spanned(
+                &fn_item.sig.ident,
+                "a component needs an rsx! macro",
+            ))
+        });
 
         // Resolve subscribers
         for i in 0..memos.len() {
             let memo = &memos[i];
             let mut subscribers = Vec::new();
             for other in memos.iter() {
-                if other.subscriptions.contains(&memo.id) {
-                    subscribers.push(memo.id);
+                if other.memo_subscriptions.contains(&memo.id) {
+                    subscribers.push(other.id);
                 }
             }
             memos[i].subscribers = subscribers.into_iter().collect();
         }
+        let memo_order = match update_order(&memos) {
+            Ok(order) => order,
+            Err(err) => {
+                errors.push(err);
+                Vec::new()
+            }
+        };
 
         for state in &mut states {
             let mut subscribers = Vec::new();
@@ -95,21 +131,27 @@ impl ComponentBuilder {
             state.subscribers = subscribers.into_iter().collect();
         }
 
-        let prop_items = fn_item
-            .sig
-            .inputs
-            .iter()
-            .skip(1)
-            .filter_map(|item| match item {
-                syn::FnArg::Typed(item) => Some(item.clone().into()),
-                syn::FnArg::Receiver(_) => None,
-            })
-            .collect();
+        let mut prop_items = Vec::new();
+        for item in fn_item.sig.inputs.iter().skip(1) {
+            if let syn::FnArg::Typed(item) = item {
+                match Prop::try_from(item.clone()) {
+                    Ok(prop) => prop_items.push(prop),
+                    Err(err) => errors.push(err),
+                }
+            }
+        }
+
+        if let Err(err) = &rsx {
+            errors.insert(0, err.clone());
+        }
+        combine(errors)?;
+        let rsx = rsx?;
 
         Ok(Component {
             type_name,
             states,
             memos,
+            memo_order,
             rsx,
             fn_item,
             prop_items,
@@ -117,9 +159,106 @@ impl ComponentBuilder {
     }
 }
 
+/// The closure of an `rx(|| ...)` call.
+fn rx_closure(expr: &Expr) -> Option<(&syn::ExprCall, Option<&syn::ExprClosure>)> {
+    let Expr::Call(call) = expr else {
+        return None;
+    };
+    let Expr::Path(ExprPath { path, .. }) = &*call.func else {
+        return None;
+    };
+    if !path.is_ident("rx") {
+        return None;
+    }
+    let closure = match call.args.first() {
+        Some(Expr::Closure(closure)) if call.args.len() == 1 => Some(closure),
+        _ => None,
+    };
+    Some((call, closure))
+}
+
+impl ComponentBuilder {
+    /// Add the memo of an `rx(|| ...)` call, `typed` is the name and type of a `let name: Memo<T> = rx(|| ...)`.
+    fn rx_memo(
+        &mut self,
+        call: &syn::ExprCall,
+        closure: Option<&syn::ExprClosure>,
+        typed: Option<(Ident, Type)>,
+        attrs: &[syn::Attribute],
+    ) {
+        let Some(closure) = closure else {
+            self.errors.push(syn::Error::new_spanned(
+                call,
+                "rx takes a single closure, rx(|| ...)",
+            ));
+            return;
+        };
+        // memos can create memos that are recreated when they rerun, state initializers run only once
+        let owner = match (self.in_reactive, self.owner) {
+            (false, _) => None,
+            (true, None) => {
+                self.errors.push(syn::Error::new_spanned(
+                    call,
+                    "state initializers cannot create memos",
+                ));
+                return;
+            }
+            (true, Some(owner)) => {
+                let message = if typed.is_some() {
+                    Some("typed memos must be created at the top of the component")
+                } else if self.memos[owner].owner.is_some() {
+                    Some("memos can only be created one level deep in another memo")
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    self.errors.push(syn::Error::new_spanned(call, message));
+                    return;
+                }
+                Some(owner)
+            }
+        };
+        // the memos of the rsx come after the memos written in the component
+        if self.rsx.is_some() {
+            self.errors.push(syn::Error::new_spanned(
+                call,
+                "rx memos must come before the rsx! macro",
+            ));
+            return;
+        }
+        let (name, ty) = typed.unzip();
+        let id = self.memo(
+            ty,
+            (*closure.body).clone(),
+            closure.capture,
+            Default::default(),
+        );
+        self.memos[id].name = name;
+        // `#[no_compare]` memos are treated as changed every time they run
+        self.memos[id].compare = !attrs.iter().any(|attr| attr.path.is_ident("no_compare"));
+        if let Some(owner) = owner {
+            self.memos[id].owner = Some(owner);
+            self.memos[owner].children.push(id);
+        }
+
+        let in_reactive = std::mem::replace(&mut self.in_reactive, true);
+        let owner = self.owner.replace(id);
+        visit::visit_expr_call(self, call);
+        self.in_reactive = in_reactive;
+        self.owner = owner;
+    }
+}
+
 impl Visit<'_> for ComponentBuilder {
     fn visit_macro(&mut self, mac: &syn::Macro) {
         if mac.path.to_token_stream().to_string() == "rsx" {
+            if self.rsx.is_some() {
+                self.errors.push(syn::Error::new_spanned(
+                    mac,
+                    "a component can only have one rsx! macro",
+                ));
+                return;
+            }
             let mut rsx = parse2::<Elements>(mac.tokens.clone());
             if let Ok(rsx) = &mut rsx {
                 rsx.construct_memos(self);
@@ -128,24 +267,29 @@ impl Visit<'_> for ComponentBuilder {
         }
     }
 
-    fn visit_expr_call(&mut self, i: &syn::ExprCall) {
-        if let Expr::Path(ExprPath { path, .. }) = &*i.func {
-            if let Some(fn_name) = path.get_ident() {
-                if fn_name == "rx" {
-                    assert!(!self.in_reactive, "nested reactivity is not supported");
-
-                    if let Some(Expr::Closure(closure)) = i.args.first().cloned() {
-                        self.memo(None, *closure.body, closure.capture, Default::default());
-
-                        self.in_reactive = true;
-                        visit::visit_expr_call(self, i);
-                        self.in_reactive = false;
-                        return;
-                    }
-                }
+    fn visit_stmt(&mut self, i: &syn::Stmt) {
+        if let syn::Stmt::Semi(expr, _) | syn::Stmt::Expr(expr) = i {
+            if let Some((call, closure)) = rx_closure(expr) {
+                self.rx_memo(call, closure, None, &call.attrs);
+                return;
             }
         }
 
+        visit::visit_stmt(self, i);
+    }
+
+    fn visit_expr_call(&mut self, i: &syn::ExprCall) {
+        // rx calls that are statements are handled in visit_stmt
+        if rx_closure(&Expr::Call(i.clone())).is_some() {
+            let message = if self.in_reactive && self.owner.is_none() {
+                "state initializers cannot create memos"
+            } else {
+                "rx(|| ...) must be a statement of its own"
+            };
+            self.errors.push(syn::Error::new_spanned(i, message));
+            return;
+        }
+
         visit::visit_expr_call(self, i);
     }
 
@@ -158,15 +302,44 @@ impl Visit<'_> for ComponentBuilder {
                     arguments: PathArguments::AngleBracketed(ty),
                 }) = segments.first()
                 {
+                    if segments.len() == 1 && ident == "Memo" {
+                        if let (Some(syn::GenericArgument::Type(ty)), Pat::Ident(name)) =
+                            (ty.args.first(), &*pat_ty.pat)
+                        {
+                            let rx = i.init.as_ref().and_then(|(_, init)| rx_closure(init));
+                            let Some((call, closure)) = rx else {
+                                self.errors.push(syn::Error::new_spanned(
+                                    &name.ident,
+                                    "a Memo is created with rx(|| ...)",
+                                ));
+                                return;
+                            };
+                            let typed = (name.ident.clone(), ty.clone());
+                            // `#[no_compare]` can go on the `let` or on the `rx` call
+                            let attrs: Vec<_> =
+                                i.attrs.iter().chain(&call.attrs).cloned().collect();
+                            self.rx_memo(call, closure, Some(typed), &attrs);
+                            return;
+                        }
+                    }
                     if segments.len() == 1 && ident == "Rx" {
                         if let Some(syn::GenericArgument::Type(ty)) = ty.args.first() {
                             if let Pat::Ident(name) = &*pat_ty.pat {
-                                assert!(!self.in_reactive, "nested reactivity is not supported");
-                                self.state(
-                                    name.ident.clone(),
-                                    ty.clone(),
-                                    *i.init.as_ref().unwrap().1.clone(),
-                                );
+                                if self.in_reactive {
+                                    self.errors.push(syn::Error::new_spanned(
+                                        i,
+                                        "reactive state must be declared at the top of the component",
+                                    ));
+                                    return;
+                                }
+                                let Some((_, init)) = &i.init else {
+                                    self.errors.push(syn::Error::new_spanned(
+                                        &name.ident,
+                                        "reactive state needs an initial value",
+                                    ));
+                                    return;
+                                };
+                                self.state(name.ident.clone(), ty.clone(), (**init).clone());
 
                                 self.in_reactive = true;
                                 visit::visit_local(self, i);
@@ -186,7 +359,10 @@ impl Visit<'_> for ComponentBuilder {
 #[derive(Debug)]
 pub struct SubscriptionVisitor<'a> {
     pub states: &'a Vec<State>,
+    // memos declared so far, code can read the memos with a name
+    pub memos: &'a [Memo],
     pub subscribed: Vec<usize>,
+    pub memos_read: Vec<usize>,
 }
 
 impl<'a, 'b> Visit<'a> for SubscriptionVisitor<'b> {
@@ -196,6 +372,11 @@ impl<'a, 'b> Visit<'a> for SubscriptionVisitor<'b> {
                 self.subscribed.push(name.id);
             }
         }
+        if let Some(memo) = self.memos.iter().find(|m| m.name.as_ref() == Some(i)) {
+            if !self.memos_read.contains(&memo.id) {
+                self.memos_read.push(memo.id);
+            }
+        }
 
         syn::visit::visit_ident(self, i);
     }
@@ -1,4 +1,6 @@
 use crate::component::Component;
+use crate::memo::Memo;
+use quote::ToTokens;
 use syn::visit_mut::{self, VisitMut};
 use syn::{parse_quote, Expr, Type};
 use syn::{ExprPath, Pat, PathArguments, PathSegment};
@@ -7,6 +9,33 @@ pub struct ComponentVisitorMut<'a> {
     pub memo_idx: usize,
     pub state_idx: usize,
     pub component: &'a Component,
+    // statements that do not match the states and memos the builder found
+    pub errors: Vec<syn::Error>,
+}
+
+impl<'a> ComponentVisitorMut<'a> {
+    /// Take the next memo the builder found if it is the one at `tokens`, or report an error there.
+    fn next_memo(
+        &mut self,
+        tokens: impl ToTokens,
+        matches: impl FnOnce(&Memo) -> bool,
+    ) -> Option<&'a Memo> {
+        let component = self.component;
+        match component.memos.get(self.memo_idx) {
+            Some(maybe_memo) if matches(maybe_memo) => {
+                // the memos it creates are made in its closure
+                self.memo_idx += 1 + maybe_memo.children.len();
+                Some(maybe_memo)
+            }
+            _ => {
+                self.errors.push(syn::Error::new_spanned(
+                    tokens,
+                    "this memo does not match the memos found in the component, create it directly in the component body or in the closure of another memo",
+                ));
+                None
+            }
+        }
+    }
 }
 
 impl VisitMut for ComponentVisitorMut<'_> {
@@ -34,15 +63,30 @@ impl VisitMut for ComponentVisitorMut<'_> {
                             if segments.len() == 1 && ident == "Rx" {
                                 if let Some(syn::GenericArgument::Type(ty)) = ty.args.first() {
                                     if let Pat::Ident(name) = &*pat_ty.pat {
-                                        let maybe_state = &self.component.states[self.state_idx];
+                                        let maybe_state = self.component.states.get(self.state_idx);
                                         self.state_idx += 1;
-                                        assert_eq!(maybe_state.name, name.ident);
-                                        assert_eq!(&maybe_state.ty, ty);
-
-                                        state = Some(maybe_state);
+                                        match maybe_state {
+                                            Some(maybe_state)
+                                                if maybe_state.name == name.ident
+                                                    && &maybe_state.ty == ty =>
+                                            {
+                                                state = Some(maybe_state)
+                                            }
+                                            _ => self.errors.push(syn::Error::new_spanned(
+                                                &name.ident,
+                                                "this state does not match the states found in the component, declare it directly in the component body",
+                                            )),
+                                        }
                                     }
                                 }
                             }
+                            if segments.len() == 1 && ident == "Memo" {
+                                if let Pat::Ident(name) = &*pat_ty.pat {
+                                    memo = self.next_memo(&name.ident, |maybe_memo| {
+                                        maybe_memo.name.as_ref() == Some(&name.ident)
+                                    });
+                                }
+                            }
                         }
                     }
                 }
@@ -52,11 +96,9 @@ impl VisitMut for ComponentVisitorMut<'_> {
                     if let Some(fn_name) = path.get_ident() {
                         if fn_name == "rx" {
                             if let Some(Expr::Closure(closure)) = expr.args.first().cloned() {
-                                let maybe_memo = &self.component.memos[self.memo_idx];
-                                self.memo_idx += 1;
-                                assert_eq!(maybe_memo.closure.as_ref().unwrap(), &*closure.body);
-
-                                memo = Some(maybe_memo);
+                                memo = self.next_memo(&closure, |maybe_memo| {
+                                    maybe_memo.closure.as_ref() == Some(&*closure.body)
+                                });
                             }
                         }
                     }
@@ -68,13 +110,15 @@ impl VisitMut for ComponentVisitorMut<'_> {
         if let Some(memo) = memo {
             *i = memo.construct(self.component);
         } else if let Some(state) = state {
-            *i = state.construct();
+            *i = state.construct(self.component);
         } else if let Some(rsx) = rsx {
             let update = self.component.rsx.update_memos(self.component);
+            let children = self.component.rsx.create_children();
             *i = parse_quote! {
                 {
                     #rsx
                     #update
+                    #children
                 }
             };
         } else {
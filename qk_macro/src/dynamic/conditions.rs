@@ -1,88 +1,169 @@
-use quote::ToTokens;
-use syn::parse::Parse;
-use syn_rsx::Node;
+use std::iter::Peekable;
 
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Expr};
+use syn_rsx::{Node, NodeElement};
+
+use super::{unwrap_block, BlockContext};
+use crate::rsx::Elements;
+
+/// An `<if>` element followed by any number of `<elif>` elements and an optional `<else>` element.
+#[derive(Debug)]
 pub struct Condition {
-    if_token: syn::Token![if],
-    condition: syn::Expr,
-    body: syn::Block,
-    else_token: Option<syn::Token![else]>,
-    else_body: Option<syn::Block>,
+    pub branches: Vec<Branch>,
+}
+
+#[derive(Debug)]
+pub struct Branch {
+    /// `None` for the `<else>` branch
+    pub condition: Option<Expr>,
+    pub children: Elements,
+}
+
+fn element<'a>(node: &'a Node, name: &str) -> Option<&'a NodeElement> {
+    match node {
+        Node::Element(element) if element.name.to_string() == name => Some(element),
+        _ => None,
+    }
+}
+
+fn next_element<'a>(
+    nodes: &mut Peekable<impl Iterator<Item = &'a Node>>,
+    name: &str,
+) -> Option<&'a NodeElement> {
+    nodes
+        .next_if(|node| element(node, name).is_some())
+        .and_then(|node| element(node, name))
+}
+
+/// The condition of an `<if>` or `<elif>` element. A missing condition is reported and treated as `false` so the children are still checked.
+fn condition(element: &NodeElement, errors: &mut Vec<syn::Error>) -> Expr {
+    match element.attributes.as_slice() {
+        [Node::Block(block)] => unwrap_block(block.value.as_ref()),
+        _ => {
+            errors.push(syn::Error::new_spanned(
+                &element.name,
+                format!("<{}> takes a single {{condition}}", element.name),
+            ));
+            parse_quote!(false)
+        }
+    }
 }
 
 impl Condition {
-    fn parse_from(nodes: impl Iterator<Item = Node>) -> Option<Self> {
-        let mut nodes = nodes.peekable();
-
-        nodes.next_if(|node| matches!(node, Node::Element(element) if element.name.to_token_stream().to_string() == "if")) .and_then(|if_element|{
-            let Node::Element(if_element) = if_element else{unreachable!()};
-            if let &[Node::Block(condition)] = &if_element.attributes.as_slice(){
-                todo!()
-            }else{None}
-         })
-        // match value {
-        //     Node::Element(element) => {
-        //         let for_token = element.name;
-        //         let mut attrs = element.attributes.into_iter();
-        //         let pat = attrs
-        //             .next()
-        //             .and_then(|attr| match attr {
-        //                 syn_rsx::Node::Attribute(attr) => match (attr.key, attr.value) {
-        //                     (NodeName::Path(path), None) => Some(path),
-        //                     _ => None,
-        //                 },
-        //                 _ => None,
-        //             })
-        //             .ok_or(())?;
-        //         let (in_token, iterator) = attrs
-        //             .next()
-        //             .and_then(|attr| match attr {
-        //                 syn_rsx::Node::Attribute(attr) => match (attr.key, attr.value) {
-        //                     (NodeName::Path(path), Some(iterator)) => {
-        //                         (path.to_token_stream().to_string() == "in")
-        //                             .then_some((path, iterator))
-        //                     }
-        //                     _ => None,
-        //                 },
-        //                 _ => None,
-        //             })
-        //             .ok_or(())?;
-        //         if attrs.next().is_some() {
-        //             return Err(());
-        //         }
-        //         Ok(Self {
-        //             for_token,
-        //             pat,
-        //             in_token,
-        //             iterator,
-        //             children: element.children,
-        //         })
-        //     }
-        //     _ => Err(()),
-        // }
+    /// Take an `<if>` element and the `<elif>` and `<else>` elements that follow it.
+    pub fn parse_from<'a>(
+        nodes: &mut Peekable<impl Iterator<Item = &'a Node>>,
+        errors: &mut Vec<syn::Error>,
+    ) -> Option<Self> {
+        let if_element = next_element(nodes, "if")?;
+
+        let mut branches = vec![Branch {
+            condition: Some(condition(if_element, errors)),
+            children: Elements::new(&if_element.children),
+        }];
+
+        while let Some(elif) = next_element(nodes, "elif") {
+            branches.push(Branch {
+                condition: Some(condition(elif, errors)),
+                children: Elements::new(&elif.children),
+            });
+        }
+
+        if let Some(else_element) = next_element(nodes, "else") {
+            if !else_element.attributes.is_empty() {
+                errors.push(syn::Error::new_spanned(
+                    &else_element.name,
+                    "<else> does not take a condition",
+                ));
+            }
+            branches.push(Branch {
+                condition: None,
+                children: Elements::new(&else_element.children),
+            });
+        }
+
+        Some(Self { branches })
+    }
+
+    /// Content that is always rendered, like the content passed to a slot.
+    pub fn always(children: Elements) -> Self {
+        Self {
+            branches: vec![Branch {
+                condition: None,
+                children,
+            }],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> impl Iterator<Item = &mut Elements> {
+        self.branches.iter_mut().map(|branch| &mut branch.children)
+    }
+
+    /// Pick the branch whose condition holds and replace the blocks of `__region` if it changed.
+    pub fn update_region(&self, cx: &BlockContext) -> TokenStream {
+        let mut select = quote! { None };
+        for (idx, branch) in self.branches.iter().enumerate().rev() {
+            select = match &branch.condition {
+                Some(condition) => quote! {
+                    if #condition { Some(#idx) } else { #select }
+                },
+                None => quote! { Some(#idx) },
+            };
+        }
+
+        let idx = 0..self.branches.len();
+        let create = self
+            .branches
+            .iter()
+            .map(|branch| branch.children.create_block(cx));
+        let idx2 = 0..self.branches.len();
+        let update = self
+            .branches
+            .iter()
+            .map(|branch| branch.children.update_block(cx));
+
+        quote! {
+            let __branch: Option<usize> = #select;
+            if __region.branch() != __branch {
+                let __blocks = match __branch {
+                    #(Some(#idx) => vec![#create],)*
+                    _ => Vec::new(),
+                };
+                __region.replace(ui, __branch, __blocks);
+            } else if let Some(__block) = __region.blocks_mut().first_mut() {
+                match __branch {
+                    #(Some(#idx2) => #update,)*
+                    _ => {}
+                }
+            }
+        }
     }
 }
 
 #[test]
 fn parses() {
-    use quote::quote;
     use syn_rsx::parse2;
 
-    // Create HTML `TokenStream`.
     let tokens = quote! {
         <if {true}>
+            "a"
         </if>
-        <elif {true}>
+        <elif {false}>
+            "b"
         </elif>
         <else>
+            "c"
         </else>
+        <p></p>
     };
 
-    // Parse the tokens into a tree of `Node`s.
-    let mut nodes = parse2(tokens).unwrap();
-    println!("{nodes:#?}");
-
-    // // Convert the `Node`s into a `Loop`.
-    // let loop_ = Loop::try_from(nodes.pop().unwrap()).unwrap();
-    // println!("{loop_:#?}");
+    let nodes = parse2(tokens).unwrap();
+    let mut nodes = nodes.iter().peekable();
+    let condition = Condition::parse_from(&mut nodes, &mut Vec::new()).unwrap();
+    assert_eq!(condition.branches.len(), 3);
+    assert!(condition.branches[2].condition.is_none());
+    // the element after the condition is left alone
+    assert!(element(nodes.next().unwrap(), "p").is_some());
 }
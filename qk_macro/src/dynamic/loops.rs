@@ -1,25 +1,116 @@
-use quote::ToTokens;
-use syn::ExprPath;
-use syn_rsx::NodeValueExpr;
-use syn_rsx::{Node, NodeName};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{Expr, ExprPath};
+use syn_rsx::{Node, NodeAttribute, NodeName};
 
+use super::{mentions, unwrap_block, BlockContext};
+use crate::rsx::Elements;
+
+/// A `<for x in {iter}>` element, which renders its children once for every item.
+///
+/// With a `key={x.id}` attribute the blocks follow their keys when items are reordered, inserted or removed.
 #[derive(Debug)]
 pub struct Loop {
-    for_token: NodeName,
     pat: ExprPath,
-    in_token: ExprPath,
-    iterator: NodeValueExpr,
-    children: Vec<Node>,
+    iterator: Expr,
+    key: Option<Expr>,
+    children: Elements,
+    // if a listener in the children uses the item, which then has to be kept in a cell that is updated when the block is reused
+    item_in_listeners: bool,
+}
+
+impl Loop {
+    pub fn children_mut(&mut self) -> &mut Elements {
+        &mut self.children
+    }
+
+    /// Update the blocks of the items that are still there, create blocks for new items and remove the blocks of items that are gone.
+    pub fn update_region(&self, cx: &BlockContext) -> TokenStream {
+        let Self {
+            pat,
+            children,
+            item_in_listeners,
+            ..
+        } = self;
+
+        let item = pat.path.get_ident().filter(|_| *item_in_listeners);
+        let Some(item) = item else {
+            return self.update_blocks(children.create_block(cx), children.update_block(cx));
+        };
+        let cell = Ident::new(&format!("__item_{}", cx.items.len()), Span::call_site());
+        let mut items = cx.items.to_vec();
+        items.push((item.clone(), cell.clone()));
+        let cx = BlockContext {
+            items: &items,
+            ..*cx
+        };
+        let create = children.create_block(&cx);
+        let update = children.update_block(&cx);
+        // the item is moved into the cell once the nodes are created or updated
+        let create = quote! {
+            {
+                let #cell = std::rc::Rc::new(std::cell::RefCell::new(None));
+                let mut __block = #create;
+                __block.set_item(#cell.clone());
+                #cell.replace(Some(#pat));
+                __block
+            }
+        };
+        let update = quote! {
+            {
+                let #cell: std::rc::Rc<std::cell::RefCell<Option<_>>> = std::rc::Rc::clone(__block.item());
+                #update
+                #cell.replace(Some(#pat));
+            }
+        };
+        self.update_blocks(create, update)
+    }
+
+    /// Match the blocks to the items, with `create` and `update` run for each item.
+    fn update_blocks(&self, create: TokenStream, update: TokenStream) -> TokenStream {
+        let Self {
+            pat, iterator, key, ..
+        } = self;
+
+        if let Some(key) = key {
+            return quote! {
+                let mut __items = Vec::new();
+                for #pat in #iterator {
+                    __items.push((#key, #pat));
+                }
+                __region.update_keyed(
+                    ui,
+                    __items,
+                    |ui, #pat| #create,
+                    |ui, __block, #pat| #update,
+                );
+            };
+        }
+
+        // without keys the blocks are matched to items by position
+        quote! {
+            let mut __len = 0;
+            let mut __new_blocks = Vec::new();
+            for #pat in #iterator {
+                match __region.blocks_mut().get_mut(__len) {
+                    Some(__block) => #update,
+                    None => __new_blocks.push(#create),
+                }
+                __len += 1;
+            }
+            __region.truncate(ui, __len);
+            __region.extend(ui, __new_blocks);
+        }
+    }
 }
 
 #[test]
 fn parses() {
-    use quote::quote;
     use syn_rsx::parse2;
 
     // Create HTML `TokenStream`.
     let tokens = quote! {
-        <for x in {0..10}>
+        <for x in {0..10} key={x}>
         </for>
     };
 
@@ -28,59 +119,92 @@ fn parses() {
     println!("{nodes:#?}");
 
     // Convert the `Node`s into a `Loop`.
-    let loop_ = Loop::try_from(nodes.pop().unwrap()).unwrap();
+    let loop_ = Loop::try_from(&nodes.pop().unwrap()).unwrap();
     println!("{loop_:#?}");
+    assert!(loop_.key.is_some());
 }
 
-impl TryFrom<Node> for Loop {
-    type Error = ();
-
-    fn try_from(value: Node) -> Result<Self, Self::Error> {
-        match value {
-            Node::Element(element) => {
-                let for_token = element.name;
-                let mut attrs = element.attributes.into_iter();
-                let pat = attrs
-                    .next()
-                    .and_then(|attr| match attr {
-                        syn_rsx::Node::Attribute(attr) => match (attr.key, attr.value) {
-                            (NodeName::Path(path), None) => Some(path),
-                            _ => None,
-                        },
-                        _ => None,
-                    })
-                    .ok_or(())?;
-                let in_token = attrs
-                    .next()
-                    .and_then(|attr| match attr {
-                        syn_rsx::Node::Attribute(attr) => match (attr.key, attr.value) {
-                            (NodeName::Path(path), None) => {
-                                (path.to_token_stream().to_string() == "in").then_some(path)
-                            }
-                            _ => None,
-                        },
-                        _ => None,
-                    })
-                    .ok_or(())?;
-                let iterator = attrs
-                    .next()
-                    .and_then(|attr| match attr {
-                        syn_rsx::Node::Block(block) => Some(block.value),
-                        _ => None,
-                    })
-                    .ok_or(())?;
-                if attrs.next().is_some() {
-                    return Err(());
+impl TryFrom<&Node> for Loop {
+    type Error = syn::Error;
+
+    fn try_from(value: &Node) -> Result<Self, Self::Error> {
+        let Node::Element(element) = value else {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "expected a <for> element",
+            ));
+        };
+        let head_error =
+            || syn::Error::new_spanned(&element.name, "expected <for pattern in {iterator}>");
+
+        let mut attrs = element.attributes.iter();
+        let pat = match attrs.next() {
+            Some(Node::Attribute(NodeAttribute {
+                key: NodeName::Path(path),
+                value: None,
+            })) => path.clone(),
+            _ => return Err(head_error()),
+        };
+        match attrs.next() {
+            Some(Node::Attribute(NodeAttribute {
+                key: NodeName::Path(path),
+                value: None,
+            })) if path.to_token_stream().to_string() == "in" => {}
+            _ => return Err(head_error()),
+        }
+        let iterator = match attrs.next() {
+            Some(Node::Block(block)) => unwrap_block(block.value.as_ref()),
+            _ => return Err(head_error()),
+        };
+        let key = match attrs.next() {
+            Some(Node::Attribute(attr)) if attr.key.to_string() == "key" => match &attr.value {
+                Some(value) => Some(value.as_ref().clone()),
+                None => {
+                    return Err(syn::Error::new_spanned(&attr.key, "expected key={expr}"));
                 }
-                Ok(Self {
-                    for_token,
-                    pat,
-                    in_token,
-                    iterator,
-                    children: element.children,
-                })
+            },
+            Some(Node::Attribute(attr)) => {
+                return Err(syn::Error::new_spanned(
+                    &attr.key,
+                    "<for> only takes a key={expr} after the iterator",
+                ));
             }
-            _ => Err(()),
+            Some(_) => return Err(head_error()),
+            None => None,
+        };
+        if attrs.next().is_some() {
+            return Err(syn::Error::new_spanned(
+                &element.name,
+                "<for> only takes a key={expr} after the iterator",
+            ));
         }
+        let item_in_listeners = pat
+            .path
+            .get_ident()
+            .is_some_and(|item| listeners_mention(&element.children, item));
+        Ok(Self {
+            pat,
+            iterator,
+            key,
+            children: Elements::new(&element.children),
+            item_in_listeners,
+        })
     }
 }
+
+/// If a listener in `nodes` or their descendants mentions `ident`.
+fn listeners_mention(nodes: &[Node], ident: &Ident) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Element(element) => {
+            element.attributes.iter().any(|attr| match attr {
+                Node::Attribute(NodeAttribute {
+                    key,
+                    value: Some(value),
+                }) => key.to_string().starts_with("on") && mentions(value.to_token_stream(), ident),
+                _ => false,
+            }) || listeners_mention(&element.children, ident)
+        }
+        Node::Fragment(fragment) => listeners_mention(&fragment.children, ident),
+        _ => false,
+    })
+}
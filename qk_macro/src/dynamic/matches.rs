@@ -0,0 +1,150 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{Expr, Pat, PatOr, Token};
+use syn_rsx::Node;
+
+use super::{unwrap_block, BlockContext};
+use crate::rsx::{combine, node_error, Elements};
+
+/// A `<match {expr}>` element whose children are `<case {pattern}>` arms. The children of the first arm that matches are rendered with the variables its pattern binds.
+#[derive(Debug)]
+pub struct Match {
+    expr: Expr,
+    arms: Vec<Arm>,
+}
+
+#[derive(Debug)]
+pub struct Arm {
+    pat: Pat,
+    children: Elements,
+}
+
+/// Patterns are written in an attribute block, which is parsed as an expression, so parse the tokens again as a pattern.
+fn pattern(block: &Expr) -> syn::Result<Pat> {
+    let tokens = unwrap_block(block).to_token_stream();
+    let mut cases = Punctuated::<Pat, Token![|]>::parse_separated_nonempty.parse2(tokens)?;
+    if cases.len() == 1 {
+        if let Some(case) = cases.pop() {
+            return Ok(case.into_value());
+        }
+    }
+    Ok(Pat::Or(PatOr {
+        attrs: Vec::new(),
+        leading_vert: None,
+        cases,
+    }))
+}
+
+impl Match {
+    pub fn children_mut(&mut self) -> impl Iterator<Item = &mut Elements> {
+        self.arms.iter_mut().map(|arm| &mut arm.children)
+    }
+
+    /// Match the expression once. Switching to another arm replaces the blocks of `__region`, staying in the same arm updates its block with the new bindings.
+    pub fn update_region(&self, cx: &BlockContext) -> TokenStream {
+        let expr = &self.expr;
+        let arms = self.arms.iter().enumerate().map(|(idx, arm)| {
+            let pat = &arm.pat;
+            let create = arm.children.create_block(cx);
+            let update = arm.children.update_block(cx);
+            quote! {
+                #pat => {
+                    if __region.branch() != Some(#idx) {
+                        let __blocks = vec![#create];
+                        __region.replace(ui, Some(#idx), __blocks);
+                    } else if let Some(__block) = __region.blocks_mut().first_mut() {
+                        #update
+                    }
+                }
+            }
+        });
+
+        quote! {
+            match #expr {
+                #(#arms)*
+                #[allow(unreachable_patterns)]
+                _ => {
+                    if __region.branch().is_some() {
+                        __region.replace(ui, None, Vec::new());
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<&Node> for Match {
+    type Error = syn::Error;
+
+    fn try_from(value: &Node) -> Result<Self, Self::Error> {
+        let Node::Element(element) = value else {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "expected a <match> element",
+            ));
+        };
+        let expr = match element.attributes.as_slice() {
+            [Node::Block(block)] => unwrap_block(block.value.as_ref()),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &element.name,
+                    "<match> takes a single {expr}",
+                ))
+            }
+        };
+
+        // report every arm that is wrong at once
+        let mut arms = Vec::new();
+        let mut errors = Vec::new();
+        for child in &element.children {
+            let arm = match child {
+                Node::Element(arm) if arm.name.to_string() == "case" => arm,
+                _ => {
+                    errors.push(node_error(
+                        child,
+                        "<match> can only contain <case> elements",
+                    ));
+                    continue;
+                }
+            };
+            let pat = match arm.attributes.as_slice() {
+                [Node::Block(block)] => pattern(block.value.as_ref()),
+                _ => Err(syn::Error::new_spanned(
+                    &arm.name,
+                    "<case> takes a single {pattern}",
+                )),
+            };
+            match pat {
+                Ok(pat) => arms.push(Arm {
+                    pat,
+                    children: Elements::new(&arm.children),
+                }),
+                Err(err) => errors.push(err),
+            }
+        }
+        combine(errors)?;
+
+        Ok(Self { expr, arms })
+    }
+}
+
+#[test]
+fn parses() {
+    use syn_rsx::parse2;
+
+    let tokens = quote! {
+        <match {value}>
+            <case {Some(1 | 2)}>"small"</case>
+            <case {Some(n) | Err(n)}>"{n}"</case>
+            <case {_}>"none"</case>
+        </match>
+    };
+
+    let nodes = parse2(tokens).unwrap();
+    let match_ = Match::try_from(&nodes[0]).unwrap();
+    assert_eq!(match_.arms.len(), 3);
+    assert!(matches!(match_.arms[1].pat, Pat::Or(_)));
+    assert!(matches!(match_.arms[2].pat, Pat::Wild(_)));
+}
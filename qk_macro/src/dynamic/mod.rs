@@ -1,2 +1,88 @@
 mod conditions;
 mod loops;
+mod matches;
+
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::quote;
+use syn::{parse_quote, Expr, ExprBlock, Stmt};
+
+pub use conditions::Condition;
+pub use loops::Loop;
+pub use matches::Match;
+
+use crate::rsx::Elements;
+use crate::state::State;
+
+/// `{expr}` in an element's attributes is parsed as a block, unwrap it so it can be used where braces would be linted.
+fn unwrap_block(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Block(ExprBlock { block, .. }) => match block.stmts.as_slice() {
+            [Stmt::Expr(expr)] => expr.clone(),
+            _ => parse_quote!(#block),
+        },
+        expr => expr.clone(),
+    }
+}
+
+/// The component a block of nodes is created in.
+pub struct BlockContext<'a> {
+    pub states: &'a [State],
+    pub comp_name: &'a Ident,
+    /// A handle to the component that listeners created inside the block read when they run, it is filled in once the component exists
+    pub handle: &'a Ident,
+    /// The items of the loops the block is in and the cells they are kept in. Listeners that use an item read it from its cell, so they see the current item after the block is reused
+    pub items: &'a [(Ident, Ident)],
+}
+
+/// If `ident` appears anywhere in `tokens`.
+pub fn mentions(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(other) => other == *ident,
+        TokenTree::Group(group) => mentions(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// A part of the rsx whose nodes are created and removed at runtime.
+#[derive(Debug)]
+pub enum Region {
+    Condition(Condition),
+    Loop(Box<Loop>),
+    Match(Box<Match>),
+    /// A `{expr}` child that evaluates to some `qk::content::Content`
+    Content(Box<Expr>),
+}
+
+impl Region {
+    pub fn content(block: &Expr) -> Self {
+        Region::Content(Box::new(unwrap_block(block)))
+    }
+
+    pub fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Elements> + '_> {
+        match self {
+            Region::Condition(condition) => Box::new(condition.children_mut()),
+            Region::Loop(loop_) => Box::new(std::iter::once(loop_.children_mut())),
+            Region::Match(match_) => Box::new(match_.children_mut()),
+            Region::Content(_) => Box::new(std::iter::empty()),
+        }
+    }
+
+    /// Update the `__region` variable, a `&mut qk::fragment::Region`.
+    pub fn update_region(&self, cx: &BlockContext) -> TokenStream {
+        let update = match self {
+            Region::Condition(condition) => condition.update_region(cx),
+            Region::Loop(loop_) => loop_.update_region(cx),
+            Region::Match(match_) => match_.update_region(cx),
+            Region::Content(expr) => quote! {
+                let mut __pieces = Vec::new();
+                qk::content::Content::<R, R>::pieces(#expr, &mut __pieces);
+                __region.update_content(ui, __pieces);
+            },
+        };
+
+        quote! {
+            #update
+            __region.mount();
+        }
+    }
+}
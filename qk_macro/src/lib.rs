@@ -8,6 +8,7 @@ mod node;
 mod prop;
 mod read_builder;
 mod rsx;
+mod schema;
 mod state;
 
 use component::Component;
@@ -16,6 +17,16 @@ use quote::quote;
 use rsx::Elements;
 use syn::parse_macro_input;
 
+/// Turn a function into a component.
+///
+/// `let name: Rx<T> = init;` declares state that listeners can write. `rx(|| ...)` runs a memo whenever the state it reads changes, and `let name: Memo<T> = rx(|| ...);` keeps the value of the memo so rsx and other memos can read it.
+/// Memos that read a `Memo<T>` only rerun when its new value is not equal to the old one, which needs `T: PartialEq`. Put `#[no_compare]` on the `let` or on the `rx` call to rerun them every time it runs instead:
+///
+/// ```ignore
+/// #[no_compare]
+/// let items: Memo<Vec<Item>> = rx(|| load(*page));
+/// let items: Memo<Vec<Item>> = #[no_compare] rx(|| load(*page));
+/// ```
 #[proc_macro_attribute]
 pub fn component(_args: TokenStream, input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as Component);
@@ -1,8 +1,9 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::quote;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use syn::token::Move;
-use syn::{parse_quote, Expr, Stmt, Type};
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, Expr, ExprPath, Stmt, Type};
 
 use crate::component::Component;
 
@@ -14,8 +15,19 @@ pub struct Memo {
     pub closure: Option<Expr>,
     pub capture: Option<Move>,
     pub subscriptions: HashSet<usize>,
+    // the memos this memo reads
+    pub memo_subscriptions: HashSet<usize>,
+    // the memos that read this memo
     pub subscribers: HashSet<usize>,
     pub raw_params: Vec<(Ident, Type)>,
+    // if dependents only rerun when the value changed, turned off with `#[no_compare]`
+    pub compare: bool,
+    // the variable of a `let name: Memo<T> = rx(|| ...)`
+    pub name: Option<Ident>,
+    // the memo whose closure creates this one, it is recreated every time the owner reruns
+    pub owner: Option<usize>,
+    // the memos created in the closure of this memo
+    pub children: Vec<usize>,
 }
 
 impl std::fmt::Debug for Memo {
@@ -31,21 +43,74 @@ impl std::fmt::Debug for Memo {
                 quote!(#block).to_string()
             })
             .field("subscriptions", &self.subscriptions)
+            .field("memo_subscriptions", &self.memo_subscriptions)
             .field("subscribers", &self.subscribers)
+            .field("owner", &self.owner)
+            .field("children", &self.children)
             .finish()
     }
 }
 
 impl Memo {
+    /// Memos without dependencies run once when the component is created. Typed memos are kept so other code can read them.
     pub fn runs_once(&self) -> bool {
-        self.subscriptions.is_empty()
+        self.subscriptions.is_empty() && self.memo_subscriptions.is_empty() && self.name.is_none()
     }
 
+    /// The name and type of the memos this memo reads, they are passed to its closure by reference.
+    fn memos_read<'a>(
+        &'a self,
+        component: &'a Component,
+    ) -> impl Iterator<Item = (&'a Ident, &'a Memo)> {
+        self.memo_subscriptions.iter().filter_map(|id| {
+            let memo = &component.memos[*id];
+            Some((memo.name.as_ref()?, memo))
+        })
+    }
+
+    /// The memos created in the closure of this memo that are kept to rerun on their own.
+    fn stored_children<'a>(&'a self, component: &'a Component) -> impl Iterator<Item = &'a Memo> {
+        self.children
+            .iter()
+            .map(|id| &component.memos[*id])
+            .filter(|memo| !memo.runs_once())
+    }
+
+    /// The memos a memo creates are returned with its value, so the component can replace the old ones.
+    fn return_ty(&self, component: &Component) -> TokenStream {
+        let ty = &self.ty;
+        if self.stored_children(component).next().is_none() {
+            return quote!(#ty);
+        }
+        let children = self
+            .stored_children(component)
+            .map(|child| child.ty(component));
+        quote! {
+            (#ty, (#(#children,)*))
+        }
+    }
+
+    /// Memos created by another memo only exist while the branch of their owner that creates them ran.
     pub fn ty(&self, component: &Component) -> TokenStream {
         let ty = &self.ty;
+        let return_ty = self.return_ty(component);
         let types = self.types(component);
-        quote! {
-            Effect<Box<dyn Fn(#types)>, #ty>
+        let tracking = component.tracking_ty();
+        let effect = quote! {
+            Effect<Box<dyn Fn(#types) -> #return_ty>, #ty, #tracking>
+        };
+        match self.owner {
+            Some(_) => quote!(Option<#effect>),
+            None => effect,
+        }
+    }
+
+    /// Check if the memo read a state or memo the last time it ran.
+    pub fn is_subscribed(&self, num: usize) -> TokenStream {
+        let ident = self.ident();
+        match self.owner {
+            Some(_) => quote!(self.#ident.as_ref().is_some_and(|memo| memo.is_subscribed(#num))),
+            None => quote!(self.#ident.is_subscribed(#num)),
         }
     }
 
@@ -62,18 +127,30 @@ impl Memo {
     }
 
     fn types(&self, component: &Component) -> TokenStream {
+        let tracking = component.tracking_ty();
         let mut parameters = Vec::new();
         for id in &self.subscriptions {
-            let ty = &component.states[*id].tracked_type();
+            let ty = &component.states[*id].tracked_type(&tracking);
             parameters.push(quote! {
                 #ty
             });
         }
+        for (_, memo) in self.memos_read(component) {
+            let ty = &memo.ty;
+            parameters.push(quote! {
+                &#ty,
+            });
+        }
         for (_, ty) in &self.raw_params {
             parameters.push(quote! {
                 #ty
             });
         }
+        if self.stored_children(component).next().is_some() {
+            parameters.push(quote! {
+                &DirtyTrackSet<#tracking, #tracking>,
+            });
+        }
         quote! {
             #(#parameters)*
         }
@@ -81,13 +158,20 @@ impl Memo {
 
     pub fn parameters(&self, component: &Component) -> TokenStream {
         let states = &component.states;
+        let tracking = component.tracking_ty();
         let mut parameters = Vec::new();
         for id in &self.subscriptions {
             let state = &states[*id];
             let name = &state.name;
             let ty = &state.ty;
             parameters.push(quote! {
-                mut #name: RwTrack<#ty, u8, u8>,
+                mut #name: RwTrack<#ty, #tracking, #tracking>,
+            });
+        }
+        for (name, memo) in self.memos_read(component) {
+            let ty = &memo.ty;
+            parameters.push(quote! {
+                #name: &#ty,
             });
         }
         for (r, ty) in &self.raw_params {
@@ -95,30 +179,126 @@ impl Memo {
                 mut #r: #ty,
             });
         }
+        if self.stored_children(component).next().is_some() {
+            parameters.push(quote! {
+                __tracking: &DirtyTrackSet<#tracking, #tracking>,
+            });
+        }
         quote! {
             #(#parameters)*
         }
     }
 
+    /// The body of the closure, with the memos it creates replaced by the code that creates them.
+    fn body(&self, component: &Component) -> TokenStream {
+        let mut closure = self.closure.clone();
+        if self.children.is_empty() {
+            return quote!(#closure);
+        }
+        if let Some(closure) = &mut closure {
+            CreateChildren {
+                children: self.children.iter(),
+                component,
+            }
+            .visit_expr_mut(closure);
+        }
+        if self.stored_children(component).next().is_none() {
+            return quote!(#closure);
+        }
+
+        let children: Vec<_> = self
+            .stored_children(component)
+            .map(|child| child.private_name())
+            .collect();
+        quote! {
+            #(let mut #children = None;)*
+            #[allow(clippy::let_unit_value)]
+            let __value = { #closure };
+            (__value, (#(#children,)*))
+        }
+    }
+
+    pub fn private_name(&self) -> Ident {
+        let ident_name = self.ident();
+        Ident::new(&format!("__{ident_name}"), ident_name.span())
+    }
+
+    /// Create a memo in the closure of its owner from the parameters of the owner, with its own reads.
+    fn create_child(&self, component: &Component) -> Stmt {
+        let body = self.body(component);
+        if self.runs_once() {
+            return parse_quote!({ #body; });
+        }
+
+        let states = &component.states;
+        let private_name = self.private_name();
+        let parameters = self.parameters(component);
+        let types = self.types(component);
+        let return_ty = self.return_ty(component);
+        let movability = &self.capture;
+        let subscribers = self
+            .subscriptions
+            .iter()
+            .map(|id| {
+                let name = &states[*id].name;
+                quote! {
+                    RwTrack {
+                        data: &mut *#name.data,
+                        tracking: #name.tracking,
+                    }
+                }
+            })
+            .chain(self.memos_read(component).map(|(name, _)| quote!(#name)));
+
+        parse_quote! {
+            #private_name = Some({
+                #[allow(clippy::unused_unit)]
+                let #private_name = Box::new(#movability |#parameters| {
+                    #body
+                }) as Box<dyn Fn(#types) -> #return_ty>;
+                let (current, rx_subscriptions) = __tracking.scoped_read(|| {
+                    #private_name(
+                        #(
+                            #subscribers,
+                        )*
+                    )
+                });
+
+                Effect {
+                    rx: #private_name,
+                    rx_subscriptions,
+                    current,
+                }
+            });
+        }
+    }
+
     pub fn construct(&self, component: &Component) -> Stmt {
+        let body = self.body(component);
         if self.runs_once() {
-            let closure = &self.closure;
-            return parse_quote!(#closure);
+            return parse_quote!(#body);
         }
 
         let states = &component.states;
-        let ident_name = self.ident();
-        let closure = &self.closure;
-        let private_name = Ident::new(&format!("__{ident_name}"), ident_name.span());
+        let private_name = self.private_name();
         let parameters = self.parameters(component);
         let types = self.types(component);
+        let return_ty = self.return_ty(component);
+        let stored_children: Vec<_> = self.stored_children(component).collect();
 
         let subscribers = self
             .subscriptions
             .iter()
-            .map(|id| states[*id].name.clone())
-            .chain(self.raw_params.iter().map(|(r, _)| r).cloned());
-        let ty = &self.ty;
+            .map(|id| {
+                let name = &states[*id].name;
+                quote!(#name)
+            })
+            .chain(self.memos_read(component).map(|(_, memo)| {
+                let private = memo.private_name();
+                quote!(&#private.current)
+            }))
+            .chain(self.raw_params.iter().map(|(r, _)| quote!(#r)))
+            .chain((!stored_children.is_empty()).then(|| quote!(&tracking)));
 
         let rw_tracks = self.subscriptions.iter().map(|id| {
             let state = &states[*id];
@@ -126,6 +306,18 @@ impl Memo {
             state.construct_tracked(parse_quote! {&mut #name})
         });
 
+        // the memos this memo created start out as the ones from its first run
+        let (current, set_children) = if stored_children.is_empty() {
+            (quote!(current), None)
+        } else {
+            let children = stored_children.iter().map(|child| child.private_name());
+            let index = (0..stored_children.len()).map(syn::Index::from);
+            (
+                quote!((current, __children)),
+                Some(quote!(#(#children = __children.#index;)*)),
+            )
+        };
+
         let movability = &self.capture;
 
         parse_quote! {
@@ -134,13 +326,14 @@ impl Memo {
                 #( #rw_tracks )*
                 #[allow(clippy::unused_unit)]
                 let #private_name = Box::new(#movability |#parameters| {
-                    #closure
-                }) as Box<dyn Fn(#types) -> #ty>;
-                let current = #private_name(
+                    #body
+                }) as Box<dyn Fn(#types) -> #return_ty>;
+                let #current = #private_name(
                     #(
                         #subscribers,
                     )*
                 );
+                #set_children
 
                 Effect {
                     rx: #private_name,
@@ -151,14 +344,20 @@ impl Memo {
         }
     }
 
+    pub fn update_fn(&self) -> Ident {
+        Ident::new(
+            &format!("update_{}", self.ident()),
+            proc_macro2::Span::call_site(),
+        )
+    }
+
+    /// Rerun the memo. Returns if its value changed, and the memos that need to rerun because it wrote to their states.
     pub fn update(&self, component: &Component) -> TokenStream {
         let states = &component.states;
         let ident_name = self.ident();
+        let tracking = component.tracking_ty();
 
-        let update_fn_name = Ident::new(
-            &format!("update_{ident_name}"),
-            proc_macro2::Span::call_site(),
-        );
+        let update_fn_name = self.update_fn();
 
         let subscriptions_setup = self
             .subscriptions
@@ -179,29 +378,163 @@ impl Memo {
         let subscriptions: Vec<_> = self
             .subscriptions
             .iter()
-            .map(|id| states[*id].name.clone())
-            .chain(self.raw_params.iter().map(|(r, _)| r).cloned())
+            .map(|id| {
+                let name = &states[*id].name;
+                quote!(#name)
+            })
+            .chain(self.memos_read(component).map(|(_, memo)| {
+                let field = memo.ident();
+                quote!(&self.#field.current)
+            }))
+            .chain(self.raw_params.iter().map(|(r, _)| quote!(#r)))
             .collect();
+        let stored_children: Vec<_> = self.stored_children(component).collect();
 
-        let subscriptions_update = self.subscriptions.iter().map(|id| states[*id].update_fn());
+        let subscriptions_dirty = self.subscriptions.iter().map(|id| states[*id].dirty_fn());
+
+        // without comparison every run counts as a change, so the value does not need to be PartialEq
+        let set = if self.compare {
+            quote!(__memo.set(current))
+        } else {
+            quote! {
+                {
+                    __memo.current = current;
+                    true
+                }
+            }
+        };
+
+        // memos created by another memo are gone if their owner did not create them the last time it ran
+        let memo = match self.owner {
+            Some(_) => quote! {
+                let Some(__memo) = &mut self.#ident_name else {
+                    return (false, Default::default());
+                };
+            },
+            None => quote!(let __memo = &mut self.#ident_name;),
+        };
+
+        // replacing the memos this memo created drops the old ones
+        let (current, tracking_arg, set_children) = if stored_children.is_empty() {
+            (quote!(current), None, None)
+        } else {
+            let children = stored_children.iter().map(|child| child.ident());
+            let index = (0..stored_children.len()).map(syn::Index::from);
+            (
+                quote!((current, __children)),
+                Some(quote!(&self.tracking,)),
+                Some(quote!(#(self.#children = __children.#index;)*)),
+            )
+        };
 
         quote! {
-            fn #update_fn_name(&mut self) {
+            #[allow(unused_mut)]
+            fn #update_fn_name(&mut self) -> (bool, #tracking) {
                 self.tracking.reset_write();
-                let old = self.#ident_name.current.clone();
                 #(
                     #subscriptions_setup
                 )*
-                self.#ident_name.current = (self.#ident_name.rx)(
+                #memo
+                let #current = (__memo.rx)(
                     #(
                         #subscriptions,
                     )*
+                    #tracking_arg
                 );
-                // if old != self.#ident_name.current {
-                //     todo!("handle memo returns");
-                // }
-                #( self.#subscriptions_update(); )*
+                let changed = #set;
+                #set_children
+                let mut written: #tracking = Default::default();
+                #( written = qk::prelude::Bits::union(written, self.#subscriptions_dirty()); )*
+                (changed, written)
+            }
+        }
+    }
+}
+
+/// The order memos update in: every memo comes after the memos it reads, so it never sees a stale value.
+pub fn update_order(memos: &[Memo]) -> syn::Result<Vec<usize>> {
+    // a memo also comes after its owner, which recreates it
+    let mut unresolved: Vec<_> = memos
+        .iter()
+        .map(|memo| memo.memo_subscriptions.len() + usize::from(memo.owner.is_some()))
+        .collect();
+    let mut ready: BTreeSet<_> = memos
+        .iter()
+        .filter(|memo| unresolved[memo.id] == 0)
+        .map(|memo| memo.id)
+        .collect();
+
+    let mut order = Vec::with_capacity(memos.len());
+    while let Some(id) = ready.pop_first() {
+        order.push(id);
+        for subscriber in memos[id].subscribers.iter().chain(&memos[id].children) {
+            unresolved[*subscriber] -= 1;
+            if unresolved[*subscriber] == 0 {
+                ready.insert(*subscriber);
             }
         }
     }
+
+    match memos.iter().find(|memo| unresolved[memo.id] > 0) {
+        Some(memo) => Err(syn::Error::new_spanned(
+            &memo.closure,
+            "memos cannot read each other in a cycle",
+        )),
+        None => Ok(order),
+    }
+}
+
+#[test]
+fn orders_chained_memos() {
+    fn memo(id: usize, reads: &[usize], read_by: &[usize]) -> Memo {
+        Memo {
+            id,
+            ty: parse_quote!(()),
+            closure: Some(parse_quote!(#id)),
+            capture: None,
+            subscriptions: HashSet::from([0]),
+            memo_subscriptions: reads.iter().copied().collect(),
+            subscribers: read_by.iter().copied().collect(),
+            raw_params: Vec::new(),
+            compare: true,
+            name: None,
+            owner: None,
+            children: Vec::new(),
+        }
+    }
+
+    // 0 -> 2 -> 1, and 3 reads both 0 and 1
+    let memos = [
+        memo(0, &[], &[2, 3]),
+        memo(1, &[2], &[3]),
+        memo(2, &[0], &[1]),
+        memo(3, &[0, 1], &[]),
+    ];
+    assert_eq!(update_order(&memos).unwrap(), [0, 2, 1, 3]);
+
+    let cycle = [memo(0, &[1], &[1]), memo(1, &[0], &[0])];
+    assert!(update_order(&cycle).is_err());
+}
+
+/// Replaces the `rx(|| ...)` statements in the closure of a memo with the code that creates its children.
+struct CreateChildren<'a> {
+    children: std::slice::Iter<'a, usize>,
+    component: &'a Component,
+}
+
+impl VisitMut for CreateChildren<'_> {
+    fn visit_stmt_mut(&mut self, i: &mut Stmt) {
+        if let Stmt::Semi(Expr::Call(call), _) | Stmt::Expr(Expr::Call(call)) = i {
+            if let Expr::Path(ExprPath { path, .. }) = &*call.func {
+                if path.is_ident("rx") {
+                    if let Some(id) = self.children.next() {
+                        *i = self.component.memos[*id].create_child(self.component);
+                    }
+                    return;
+                }
+            }
+        }
+
+        visit_mut::visit_stmt_mut(self, i);
+    }
 }
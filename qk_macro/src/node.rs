@@ -2,9 +2,9 @@ use proc_macro2::{Ident, TokenStream};
 use quote::{quote, ToTokens};
 use syn::visit::Visit;
 use syn::{parse_quote, Expr, ExprClosure};
-use syn_rsx::NodeValueExpr;
 
 use crate::component_visitor::SubscriptionVisitor;
+use crate::dynamic::{mentions, Region};
 use crate::format::FormattedText;
 use crate::rsx::Root;
 use crate::state::State;
@@ -27,6 +27,82 @@ impl DynamicNode {
         )
     }
 
+    /// The `Rc<RefCell<qk::fragment::Region>>` that holds the blocks of a region node.
+    pub fn region_ident(&self) -> Option<Ident> {
+        let DynamicNodeType::Region(_) = &self.node else {
+            return None;
+        };
+        let id = self.id;
+        let root_id = self.root_id;
+        Some(Ident::new(
+            &format!("__region_{root_id}_{id}"),
+            proc_macro2::Span::call_site(),
+        ))
+    }
+
+    /// The `qk::component::DynComponentState` of a component or slot node.
+    pub fn child_ident(&self) -> Option<Ident> {
+        let (DynamicNodeType::Component(_) | DynamicNodeType::Slot(_)) = &self.node else {
+            return None;
+        };
+        let id = self.id;
+        let root_id = self.root_id;
+        Some(Ident::new(
+            &format!("__child_{root_id}_{id}"),
+            proc_macro2::Span::call_site(),
+        ))
+    }
+
+    /// The `qk::component::Children` the `idx`th slot of a component node is rendered into.
+    pub fn slot_ident(&self, idx: usize) -> Ident {
+        let id = self.id;
+        let root_id = self.root_id;
+        Ident::new(
+            &format!("__slot_{root_id}_{id}_{idx}"),
+            proc_macro2::Span::call_site(),
+        )
+    }
+
+    /// The state of a component or slot node, or `None` for other nodes. The `Children` of the slots of a component must already be created.
+    pub fn create_child(&self) -> Option<Expr> {
+        match &self.node {
+            DynamicNodeType::Component(component) => {
+                let name = &component.name;
+                let props = component
+                    .props
+                    .iter()
+                    .map(|(prop, value)| quote!(#prop: #value));
+                let slots = component.slots.iter().enumerate().map(|(idx, (prop, _))| {
+                    let slot = self.slot_ident(idx);
+                    quote!(#prop: #slot.clone())
+                });
+                Some(parse_quote! {
+                    qk::component::Component::create(#name { #(#props,)* #(#slots,)* }, ui)
+                })
+            }
+            DynamicNodeType::Slot(prop) => Some(parse_quote!(#prop.clone())),
+            _ => None,
+        }
+    }
+
+    /// Insert the nodes of a component or slot in front of its anchor. Top level components and slots are inserted with the roots of their parent instead.
+    pub fn insert_child(&self, state: &Ident) -> TokenStream {
+        if self.path.is_empty() {
+            return quote! {};
+        }
+        let anchor = self.ident();
+        match &self.node {
+            DynamicNodeType::Slot(prop) => quote! {
+                #prop.insert_before(ui, #anchor);
+            },
+            _ => quote! {
+                for __root in qk::component::ComponentState::roots(&#state) {
+                    ui.insert_before(#anchor, __root);
+                }
+            },
+        }
+    }
+
     pub fn type_def(&self) -> TokenStream {
         let name = self.ident();
         quote! {
@@ -39,17 +115,55 @@ impl DynamicNode {
             for listener in &mut element.listeners {
                 let mut subscribers = SubscriptionVisitor {
                     states,
+                    memos: &[],
                     subscribed: Vec::new(),
+                    memos_read: Vec::new(),
                 };
                 subscribers.visit_expr_closure(&listener.value);
 
                 listener.states_used = subscribers.subscribed;
             }
         }
+        if let DynamicNodeType::Region(region) = &mut self.node {
+            for children in region.children_mut() {
+                children.complete_listeners(states);
+            }
+        }
+        if let DynamicNodeType::Component(component) = &mut self.node {
+            for (_, region) in &mut component.slots {
+                for children in region.children_mut() {
+                    children.complete_listeners(states);
+                }
+            }
+        }
     }
 
-    pub fn listeners(&self, states: &[State], ty: &Ident) -> Option<Expr> {
+    /// Add the listeners of this node. Listeners in a block get the component from `handle` because the component may not exist yet when they are added.
+    pub fn listeners(
+        &self,
+        states: &[State],
+        ty: &Ident,
+        handle: Option<&Ident>,
+        items: &[(Ident, Ident)],
+    ) -> Option<Expr> {
         let id = self.ident();
+        let (get_comp, borrow_comp) = match handle {
+            Some(handle) => (
+                quote!(#handle.clone()),
+                quote! {
+                    let Some(comp) = comp.borrow().clone() else {
+                        return;
+                    };
+                    let mut comp = comp.borrow_mut();
+                },
+            ),
+            None => (
+                quote!(comp.clone()),
+                quote! {
+                    let mut comp = comp.borrow_mut();
+                },
+            ),
+        };
         match &self.node {
             DynamicNodeType::Element(element) => {
                 if element.listeners.is_empty() {
@@ -85,17 +199,34 @@ impl DynamicNode {
                             &state.name
                         });
 
-                        let update_maybe_writes = listener.states_used.iter().map(|id| states[*id].update_fn());
+                        // memos that read any of the states the listener wrote run once, after the listener
+                        let dirty_fns = listener.states_used.iter().map(|id| states[*id].dirty_fn());
+                        let run_memos = (!listener.states_used.is_empty()).then(|| quote! {
+                            let dirty = [#(comp.#dirty_fns(),)*]
+                                .into_iter()
+                                .fold(Default::default(), qk::prelude::Bits::union);
+                            comp.run_memos(dirty);
+                        });
+
+                        // loop items are read from the cell of their block when the listener runs
+                                        let (cells, items): (Vec<_>, Vec<_>) = items
+                            .iter()
+                            .filter(|(item, _)| mentions(listener.value.to_token_stream(), item))
+                            .map(|(item, cell)| (cell, item))
+                            .unzip();
 
                         quote! {
                             ui.add_listener(#id, qk::events::#as_ident, Box::new({
-                                let comp = comp.clone();
+                                let comp = #get_comp;
+                                #(let #cells = #cells.clone();)*
                                 #(#attrs)* move #asyncness #capture #or1_token #(#inputs,)* #or2_token #output {
-                                    let mut comp = comp.borrow_mut();
+                                    #(let #items = std::clone::Clone::clone(#cells.borrow().as_ref().unwrap());)*
+                                    #borrow_comp
                                     let #ty{#(#rw_names,)* tracking, ui, ..} = &mut *comp;
+                                    tracking.reset_write();
                                     #(#rw_tracks)*
                                     #body;
-                                    #(comp.#update_maybe_writes();)*
+                                    #run_memos
                                     comp.ui.flush();
                                 }
                             }));
@@ -124,8 +255,20 @@ impl DynamicNode {
                 let attributes = element.attributes.iter().map(|attribute| {
                     let key = &attribute.key;
                     let value = &attribute.value;
+                    let set = match attribute.namespace {
+                        Some(namespace) => quote! {
+                            ui.set_attribute_ns(#id, #key, #namespace, &value)
+                        },
+                        None => quote! {
+                            ui.set_attribute(#id, #key, &value)
+                        },
+                    };
+                    // `bool` and `Option` values remove the attribute instead of setting it
                     quote! {
-                        ui.set_attribute(#id, #key, &#value);
+                        match qk::renderer::AttributeValue::value(&#value) {
+                            Some(value) => #set,
+                            None => ui.remove_attribute(#id, #key),
+                        }
                     }
                 });
 
@@ -143,9 +286,9 @@ impl DynamicNode {
                     }
                 })
             }
-            DynamicNodeType::Fragment(_) => {
-                todo!()
-            }
+            // regions are updated by a memo of their own
+            DynamicNodeType::Region(_) => None,
+            DynamicNodeType::Component(_) | DynamicNodeType::Slot(_) => None,
         }
     }
 }
@@ -154,7 +297,10 @@ impl DynamicNode {
 pub enum DynamicNodeType {
     Element(DynElement),
     Text(DynText),
-    Fragment(DynFragment),
+    Region(Region),
+    Component(DynComponent),
+    /// `<slot />` or `<slot:name />`, where the `Children` prop with that name is rendered
+    Slot(Ident),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -180,6 +326,7 @@ pub struct Listener {
 #[derive(Debug)]
 pub struct DynamicAttribute {
     pub key: String,
+    pub namespace: Option<&'static str>,
     pub value: Expr,
 }
 
@@ -188,9 +335,13 @@ pub struct DynText {
     pub text: FormattedText,
 }
 
+/// A component used like an element, `<TakesProp number=42 />`.
 #[derive(Debug)]
-pub struct DynFragment {
-    pub children: NodeValueExpr,
+pub struct DynComponent {
+    pub name: syn::Path,
+    pub props: Vec<(Ident, Expr)>,
+    /// The `Children` props the caller passes content to, the content is updated like a region with a single block
+    pub slots: Vec<(Ident, Region)>,
 }
 
 pub fn update_dyn_nodes(roots: &[Root]) -> proc_macro2::TokenStream {
@@ -316,7 +467,7 @@ pub fn update_dyn_nodes(roots: &[Root]) -> proc_macro2::TokenStream {
             .unwrap();
 
         quote! {
-            ui.clone_node(unsafe{tmpl.get_unchecked(#i).load(std::sync::atomic::Ordering::Relaxed)}, #root_name);
+            ui.clone_node(tmpl[#i], #root_name);
         }
     });
 
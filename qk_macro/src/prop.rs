@@ -7,16 +7,24 @@ pub struct Prop {
     pub(crate) options: Vec<PropOption>,
 }
 
-impl From<PatType> for Prop {
-    fn from(arg: PatType) -> Self {
-        Self {
-            name: match *arg.pat {
-                Pat::Ident(ref pat) => pat.ident.clone(),
-                _ => todo!(),
-            },
+impl TryFrom<PatType> for Prop {
+    type Error = syn::Error;
+
+    fn try_from(arg: PatType) -> syn::Result<Self> {
+        let name = match *arg.pat {
+            Pat::Ident(ref pat) => pat.ident.clone(),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg.pat,
+                    "props must be named with an identifier",
+                ))
+            }
+        };
+        Ok(Self {
+            name,
             ty: *arg.ty,
             options: Default::default(),
-        }
+        })
     }
 }
 
@@ -24,7 +32,7 @@ impl Parse for Prop {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let arg: syn::FnArg = input.parse()?;
         if let syn::FnArg::Typed(arg) = arg {
-            Ok(arg.into())
+            arg.try_into()
         } else {
             Err(syn::Error::new_spanned(arg, "expected typed argument"))
         }
@@ -1,19 +1,25 @@
+use std::fmt::Display;
 use std::str::FromStr;
 
 use crate::{
     component::Component,
-    component_visitor::ComponentBuilder,
+    component_visitor::{ComponentBuilder, SubscriptionVisitor},
+    dynamic::{BlockContext, Condition, Loop, Match, Region},
     format::{FormattedSegment, FormattedText, Segment},
     node::{
-        self, update_dyn_nodes, DynElement, DynText, DynamicAttribute, DynamicNode, Listener,
-        TraverseOperation,
+        self, update_dyn_nodes, DynComponent, DynElement, DynText, DynamicAttribute, DynamicNode,
+        DynamicNodeType, Listener, TraverseOperation,
     },
+    schema,
 };
-use proc_macro2::{Ident, TokenStream};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::{quote, ToTokens};
 use slotmap::{DefaultKey, Key, SlotMap};
-use syn::{parse::Parse, parse_quote, Expr, ExprLit, Lit, Stmt};
-use syn_rsx::{Node, NodeAttribute, NodeElement, NodeText, ParserConfig};
+use syn::{parse::Parse, parse_quote, visit::Visit, Expr, ExprLit, Lit, Path, Stmt};
+use syn_rsx::{
+    Node, NodeAttribute, NodeBlock, NodeComment, NodeDoctype, NodeElement, NodeName, NodeText,
+    ParserConfig,
+};
 
 #[derive(Debug)]
 pub struct Elements {
@@ -21,14 +27,33 @@ pub struct Elements {
     pub roots: Vec<Root>,
     creation: proc_macro2::TokenStream,
     current_path: Vec<TraverseOperation>,
+    // the namespace elements are created in, None for HTML
+    namespace: Option<&'static str>,
     memo_ids: Vec<usize>,
+    // the `<!DOCTYPE>` at the start of a component's rsx
+    doctype: Option<String>,
+    // unsupported nodes found while building, including the errors of nested regions
+    errors: Vec<syn::Error>,
 }
 
 impl Parse for Elements {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let elements = syn_rsx::Parser::new(ParserConfig::default()).parse(input)?;
+        let mut elements = syn_rsx::Parser::new(ParserConfig::default()).parse(input)?;
+
+        let doctype = match elements.first() {
+            Some(Node::Doctype(doctype)) => {
+                Some(doctype.value.as_ref().to_token_stream().to_string())
+            }
+            _ => None,
+        };
+        if doctype.is_some() {
+            elements.remove(0);
+        }
 
-        Ok(Elements::new(&elements))
+        let mut elements = Elements::new(&elements);
+        elements.doctype = doctype;
+        combine(std::mem::take(&mut elements.errors))?;
+        Ok(elements)
     }
 }
 
@@ -37,32 +62,196 @@ impl ToTokens for Elements {
         let get_template_fn = self.get_template_fn();
         let update_dynamic_nodes = update_dyn_nodes(&self.roots);
 
+        let regions = self.dynamic_nodes().filter_map(|node| {
+            let region = node.region_ident()?;
+            let region_rx = rx_ident(&region);
+            let handle = handle_ident(&region);
+            let anchor = node.ident();
+            let mounted = !node.path.is_empty();
+            Some(quote! {
+                #region = std::rc::Rc::new(std::cell::RefCell::new(qk::fragment::Region::new(#anchor, #mounted)));
+                let #region_rx = #region.clone();
+                let #handle = __comp_handle.clone();
+            })
+        });
+
+        // the slots are filled in by the memos of this component before the child is created
+        let slots = self.dynamic_nodes().map(|node| {
+            let slots = slot_idents(node);
+            let rx = slots.iter().map(rx_ident);
+            let handle = slots.iter().map(handle_ident);
+            quote! {
+                #(
+                    let #slots = qk::component::Children::new(ui);
+                    let #rx = #slots.region().clone();
+                    let #handle = __comp_handle.clone();
+                )*
+            }
+        });
+
+        let doctype = self.doctype.iter();
+
         tokens.extend(quote! {
+            #(ui.set_doctype(#doctype);)*
             #get_template_fn
             #update_dynamic_nodes
+            #(#slots)*
+            #(#regions)*
         });
     }
 }
 
+/// A node or a group of sibling nodes that are built together.
+enum Item<'a> {
+    Node(&'a Node),
+    Region(Region),
+}
+
+/// Replace `<>...</>` fragments with their children.
+fn flatten<'a>(nodes: impl IntoIterator<Item = &'a Node>, flat: &mut Vec<&'a Node>) {
+    for node in nodes {
+        match node {
+            Node::Fragment(fragment) => flatten(&fragment.children, flat),
+            node => flat.push(node),
+        }
+    }
+}
+
+/// Move the errors of the elements inside a region to `errors`.
+fn region_errors(region: &mut Region, errors: &mut Vec<syn::Error>) {
+    for children in region.children_mut() {
+        errors.append(&mut children.errors);
+    }
+}
+
+fn items<'a>(
+    nodes: impl IntoIterator<Item = &'a Node>,
+    errors: &mut Vec<syn::Error>,
+) -> Vec<Item<'a>> {
+    let mut flat = Vec::new();
+    flatten(nodes, &mut flat);
+    let mut nodes = flat.into_iter().peekable();
+    let mut items = Vec::new();
+    while nodes.peek().is_some() {
+        let region = match Condition::parse_from(&mut nodes, errors) {
+            Some(condition) => Ok(Region::Condition(condition)),
+            None => {
+                let Some(node) = nodes.next() else {
+                    break;
+                };
+                match node {
+                    Node::Element(element) if element.name.to_string() == "for" => {
+                        Loop::try_from(node).map(|loop_| Region::Loop(Box::new(loop_)))
+                    }
+                    Node::Element(element) if element.name.to_string() == "match" => {
+                        Match::try_from(node).map(|match_| Region::Match(Box::new(match_)))
+                    }
+                    node => {
+                        items.push(Item::Node(node));
+                        continue;
+                    }
+                }
+            }
+        };
+        match region {
+            Ok(mut region) => {
+                region_errors(&mut region, errors);
+                items.push(Item::Region(region));
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    items
+}
+
+/// Combine errors so they are reported together, `Ok` if there are none.
+pub fn combine(errors: impl IntoIterator<Item = syn::Error>) -> syn::Result<()> {
+    let mut errors = errors.into_iter();
+    let Some(mut error) = errors.next() else {
+        return Ok(());
+    };
+    for other in errors {
+        error.combine(other);
+    }
+    Err(error)
+}
+
+/// An error pointing at the tokens of a node.
+pub fn node_error(node: &Node, message: impl Display) -> syn::Error {
+    match node {
+        Node::Element(element) => syn::Error::new_spanned(&element.name, message),
+        Node::Attribute(attr) => syn::Error::new_spanned(&attr.key, message),
+        Node::Text(NodeText { value })
+        | Node::Comment(NodeComment { value })
+        | Node::Doctype(NodeDoctype { value })
+        | Node::Block(NodeBlock { value }) => syn::Error::new_spanned(value.as_ref(), message),
+        Node::Fragment(fragment) => match fragment.children.first() {
+            Some(child) => node_error(child, message),
+            None => syn::Error::new(Span::call_site(), message),
+        },
+    }
+}
+
+fn slot_idents(node: &DynamicNode) -> Vec<Ident> {
+    match &node.node {
+        DynamicNodeType::Component(component) => (0..component.slots.len())
+            .map(|idx| node.slot_ident(idx))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Every region a block or component updates in order: the regions in the template followed by the slots of each component.
+fn regions(nodes: &[Root]) -> Vec<(&DynamicNode, Option<usize>, &Region)> {
+    let mut regions = Vec::new();
+    for node in nodes.iter().flat_map(|root| &root.dynamic_nodes) {
+        match &node.node {
+            DynamicNodeType::Region(region) => regions.push((node, None, region)),
+            DynamicNodeType::Component(component) => {
+                for (idx, (_, region)) in component.slots.iter().enumerate() {
+                    regions.push((node, Some(idx), region));
+                }
+            }
+            _ => {}
+        }
+    }
+    regions
+}
+
+/// The memo of a region clones the region and the component handle so the originals can be stored in the component.
+fn rx_ident(region: &Ident) -> Ident {
+    Ident::new(&format!("{region}_rx"), region.span())
+}
+
+fn handle_ident(region: &Ident) -> Ident {
+    Ident::new(&format!("{region}_handle"), region.span())
+}
+
 impl Elements {
-    fn new(elements: &[Node]) -> Self {
+    pub fn new<'a>(elements: impl IntoIterator<Item = &'a Node>) -> Self {
         let mut myself = Self {
             slots: SlotMap::new(),
             creation: Default::default(),
             roots: Default::default(),
             current_path: Default::default(),
+            namespace: None,
             memo_ids: Default::default(),
+            doctype: None,
+            errors: Vec::new(),
         };
 
-        for (idx, element) in elements.iter().enumerate() {
+        for item in items(elements, &mut myself.errors) {
             let mut root = Root {
-                idx,
+                idx: myself.roots.len(),
                 dynamic_nodes: Default::default(),
                 root_name: None,
             };
-            let nodes = myself.build_node(&mut root, element, true);
-            assert_eq!(nodes.len(), 1);
-            root.root_name = Some(match &nodes[0] {
+            let nodes = myself.build_item(&mut root, item, true);
+            // nodes that could not be built were reported as errors
+            let [node] = nodes.as_slice() else {
+                continue;
+            };
+            root.root_name = Some(match node {
                 QkNode::Static(id) => node_ident(*id).to_token_stream(),
                 QkNode::Dynamic(id) => id.to_token_stream(),
             });
@@ -72,11 +261,49 @@ impl Elements {
         myself
     }
 
-    pub fn construct_memos(&mut self, builder: &mut ComponentBuilder) {
-        // Go through all dynamic nodes and create memos for them
+    fn dynamic_nodes(&self) -> impl Iterator<Item = &DynamicNode> {
+        self.roots.iter().flat_map(|root| &root.dynamic_nodes)
+    }
+
+    /// If there are regions or slots whose blocks need a handle to the component.
+    pub fn has_regions(&self) -> bool {
+        !regions(&self.roots).is_empty()
+    }
+
+    pub fn complete_listeners(&mut self, states: &Vec<crate::state::State>) {
         for root in &mut self.roots {
             for dyn_node in &mut root.dynamic_nodes {
-                dyn_node.complete_listeners(&builder.states);
+                dyn_node.complete_listeners(states);
+            }
+        }
+    }
+
+    pub fn construct_memos(&mut self, builder: &mut ComponentBuilder) {
+        let comp_name = Ident::new(
+            &format!("{}State", builder.type_name),
+            builder.type_name.span(),
+        );
+
+        self.complete_listeners(&builder.states);
+
+        // Regions and slots update their blocks in a memo of their own
+        for (node, slot, region) in regions(&self.roots) {
+            let ident = match slot {
+                Some(idx) => node.slot_ident(idx),
+                None => node.region_ident().unwrap(),
+            };
+            let memo_id = builder.memo(
+                None,
+                region_memo(region, &ident, &comp_name, &builder.states),
+                Some(parse_quote! { move }),
+                vec![(parse_quote!(ui), parse_quote!(&mut R))],
+            );
+            self.memo_ids.push(memo_id);
+        }
+
+        // Go through all dynamic nodes and create memos for them
+        for root in &self.roots {
+            for dyn_node in &root.dynamic_nodes {
                 if let Some(update) = dyn_node.update() {
                     let memo_id = builder.memo(
                         None,
@@ -107,6 +334,57 @@ impl Elements {
         }
     }
 
+    /// Create the child components in order once the rest of the nodes are filled in.
+    ///
+    /// Every root before the first one that still has a child to create is finished, the renderer is told each time that list grows.
+    pub fn create_children(&self) -> TokenStream {
+        let children: Vec<_> = self
+            .roots
+            .iter()
+            .flat_map(|root| root.dynamic_nodes.iter().map(move |node| (root.idx, node)))
+            .filter(|(_, node)| node.child_ident().is_some())
+            .collect();
+        let finished_before = |created: usize| {
+            children
+                .get(created)
+                .map_or(self.roots.len(), |(root, _)| *root)
+        };
+
+        let roots_finished = |finished: usize| {
+            let roots_ident = Ident::new("__roots", proc_macro2::Span::call_site());
+            let push = self.roots[..finished]
+                .iter()
+                .map(|root| root.push_roots(&quote!(), &roots_ident));
+            quote! {
+                {
+                    let mut #roots_ident = Vec::new();
+                    #(#push)*
+                    ui.roots_finished(&#roots_ident);
+                }
+            }
+        };
+
+        let mut tokens = TokenStream::new();
+        let mut finished = finished_before(0);
+        if finished > 0 {
+            tokens.extend(roots_finished(finished));
+        }
+        for (idx, (_, node)) in children.iter().enumerate() {
+            let child = node.child_ident().unwrap();
+            let create = node.create_child().unwrap();
+            let insert = node.insert_child(&child);
+            tokens.extend(quote! {
+                #child = qk::component::DynComponentState::new(#create);
+                #insert
+            });
+            if finished_before(idx + 1) > finished {
+                finished = finished_before(idx + 1);
+                tokens.extend(roots_finished(finished));
+            }
+        }
+        tokens
+    }
+
     fn get_template_fn(&self) -> TokenStream {
         let creation = &self.creation;
         let roots = &self.roots;
@@ -116,42 +394,307 @@ impl Elements {
             .map(|root| root.root_name.as_ref().unwrap())
             .collect();
 
-        let arr_len = roots.len();
+        quote! {
+            fn get_template<R2: qk::renderer::Renderer<R2> + qk::events::PlatformEvents>(mut ui: &mut R2) -> Vec<u32> {
+                // Each template is cached by the renderer that created it
+                struct Template;
+                let key = std::any::TypeId::of::<Template>();
 
-        let tuple_idxs: Vec<_> = (0..roots.len()).collect();
+                if let Some(template) = ui.get_template(key) {
+                    return template;
+                }
 
-        let create_arr = (0..roots.len()).map(|_| quote! { std::sync::atomic::AtomicU32::new(0) });
+                #creation
+                let template = vec![#(#return_roots,)*];
+                ui.set_template(key, template.clone());
 
-        quote! {
-            fn get_template<R2: qk::renderer::Renderer<R2> + qk::events::PlatformEvents>(mut ui: &mut R2) -> &'static [std::sync::atomic::AtomicU32; #arr_len] {
-                static TEMPLATE: [std::sync::atomic::AtomicU32; #arr_len] = [#(#create_arr,)*];
-
-                if unsafe{TEMPLATE.get_unchecked(0)}.load(std::sync::atomic::Ordering::Relaxed) == 0 {
-                    #creation
-                    // Safety: Checked index at compile time
-                    unsafe {
-                        #(
-                            TEMPLATE.get_unchecked(#tuple_idxs).store(#return_roots, std::sync::atomic::Ordering::Relaxed);
-                        )*
+                template
+            }
+        }
+    }
+
+    /// Create the nodes of a block: a clone of the template with its own dynamic nodes, regions and listeners.
+    ///
+    /// The block is updated once before it is returned, like the memos of the component run once when they are created.
+    pub fn create_block(&self, cx: &BlockContext) -> TokenStream {
+        let get_template_fn = self.get_template_fn();
+        let traverse = update_dyn_nodes(&self.roots);
+        let ids: Vec<_> = self.dynamic_nodes().map(|node| node.ident()).collect();
+
+        let slots: Vec<_> = self.dynamic_nodes().flat_map(slot_idents).collect();
+
+        let regions = regions(&self.roots)
+            .into_iter()
+            .map(|(node, slot, _)| match slot {
+                Some(idx) => {
+                    let slot = node.slot_ident(idx);
+                    quote!(#slot.region().clone())
+                }
+                None => {
+                    let anchor = node.ident();
+                    let mounted = !node.path.is_empty();
+                    quote! {
+                        std::rc::Rc::new(std::cell::RefCell::new(qk::fragment::Region::new(#anchor, #mounted)))
                     }
                 }
+            });
+
+        let children = self.dynamic_nodes().filter_map(|node| {
+            let anchor = node.ident();
+            let create = node.create_child()?;
+            let state = Ident::new("__child", proc_macro2::Span::call_site());
+            let insert = node.insert_child(&state);
+            Some(quote! {
+                {
+                    let #state = #create;
+                    #insert
+                    qk::fragment::Child::new::<R, R, _>(#anchor, #state)
+                }
+            })
+        });
+
+        let mut region_idx = 0usize;
+        let mut child_idx = 0usize;
+        let mut block_roots = Vec::new();
+        for node in self.dynamic_nodes() {
+            let root = match &node.node {
+                DynamicNodeType::Region(_) => {
+                    let idx = region_idx;
+                    region_idx += 1;
+                    quote!(qk::fragment::BlockRoot::Region(#idx))
+                }
+                DynamicNodeType::Component(component) => {
+                    let idx = child_idx;
+                    child_idx += 1;
+                    region_idx += component.slots.len();
+                    quote!(qk::fragment::BlockRoot::Child(#idx))
+                }
+                DynamicNodeType::Slot(_) => {
+                    let idx = child_idx;
+                    child_idx += 1;
+                    quote!(qk::fragment::BlockRoot::Child(#idx))
+                }
+                _ => {
+                    let ident = node.ident();
+                    quote!(qk::fragment::BlockRoot::Node(#ident))
+                }
+            };
+            if node.path.is_empty() {
+                block_roots.push(root);
+            }
+        }
+
+        let listeners = self
+            .dynamic_nodes()
+            .filter_map(|node| node.listeners(cx.states, cx.comp_name, Some(cx.handle), cx.items));
+        let update = self.update_block(cx);
+
+        quote! {
+            {
+                #get_template_fn
+                #(let #ids: u32;)*
+                #traverse
+                #(let #slots = qk::component::Children::new(ui);)*
+                let mut __block = qk::fragment::Block::new(
+                    vec![#(#ids,)* #(#slots.anchor(),)*],
+                    vec![#(#regions,)*],
+                    vec![#(#children,)*],
+                    vec![#(#block_roots,)*],
+                );
+                #(#listeners)*
+                #update
+                __block
+            }
+        }
+    }
+
+    /// Update the dynamic nodes and regions of the `__block` variable.
+    pub fn update_block(&self, cx: &BlockContext) -> TokenStream {
+        let mut nodes = Vec::new();
+        let mut updates = Vec::new();
+        for (idx, node) in self.dynamic_nodes().enumerate() {
+            if let Some(update) = node.update() {
+                let ident = node.ident();
+                nodes.push(quote! {
+                    let #ident = __block.node(#idx);
+                });
+                updates.push(update.into_token_stream());
+            }
+        }
+        for (idx, (_, _, region)) in regions(&self.roots).into_iter().enumerate() {
+            let update = region.update_region(cx);
+            updates.push(quote! {
+                {
+                    let mut __region = __block.region(#idx).borrow_mut();
+                    let __region = &mut *__region;
+                    #update
+                }
+            });
+        }
 
-                &TEMPLATE
+        quote! {
+            {
+                #(#nodes)*
+                #(#updates)*
             }
         }
     }
 
+    fn build_item(&mut self, root: &mut Root, item: Item, force_dyn: bool) -> Vec<QkNode> {
+        match item {
+            Item::Node(node) => self.build_node(root, node, force_dyn),
+            Item::Region(region) => vec![QkNode::Static(self.build_region(root, region))],
+        }
+    }
+
+    /// Regions are an empty text node in the template that their blocks are inserted in front of.
+    fn build_region(&mut self, root: &mut Root, region: Region) -> DefaultKey {
+        let id = self.slots.insert(());
+        let ident = node_ident(id);
+
+        self.creation.extend(quote! {
+            let #ident = ui.node();
+            ui.create_text(#ident, "");
+        });
+
+        let dyn_id = root.dynamic_nodes.len();
+        root.dynamic_nodes.push(DynamicNode {
+            root_id: root.idx,
+            id: dyn_id,
+            path: self.current_path.clone(),
+            node: DynamicNodeType::Region(region),
+        });
+
+        id
+    }
+
+    /// Components are created in front of an empty text node in the template, like regions.
+    fn build_component(
+        &mut self,
+        root: &mut Root,
+        element: &NodeElement,
+        name: Path,
+    ) -> DefaultKey {
+        let id = self.slots.insert(());
+        let ident = node_ident(id);
+
+        self.creation.extend(quote! {
+            let #ident = ui.node();
+            ui.create_text(#ident, "");
+        });
+
+        let mut props = Vec::new();
+        for attr in &element.attributes {
+            let Node::Attribute(attr) = attr else {
+                self.errors
+                    .push(node_error(attr, "components only take key={value} props"));
+                continue;
+            };
+            let Some(value) = &attr.value else {
+                self.errors
+                    .push(syn::Error::new_spanned(&attr.key, "props need a value"));
+                continue;
+            };
+            // keep the span of the key so type errors on a prop point at it
+            let Ok(key) = syn::parse2::<Ident>(attr.key.to_token_stream()) else {
+                self.errors.push(syn::Error::new_spanned(
+                    &attr.key,
+                    "component props must be identifiers like `name={value}`",
+                ));
+                continue;
+            };
+            props.push((key, value.as_ref().clone()));
+        }
+
+        // `<slot:name>` children fill the slot with that name, the other children are passed as `children`
+        let mut slots = Vec::new();
+        let mut children = Vec::new();
+        for child in &element.children {
+            match child {
+                Node::Element(slot) => match slot_name(&slot.name) {
+                    Some(name) => {
+                        slots.push((name, slot_content(&slot.children, &mut self.errors)))
+                    }
+                    None => children.push(child),
+                },
+                _ => children.push(child),
+            }
+        }
+        if !children.is_empty() {
+            slots.push((
+                Ident::new("children", proc_macro2::Span::call_site()),
+                slot_content(children, &mut self.errors),
+            ));
+        }
+
+        let dyn_id = root.dynamic_nodes.len();
+        root.dynamic_nodes.push(DynamicNode {
+            root_id: root.idx,
+            id: dyn_id,
+            path: self.current_path.clone(),
+            node: DynamicNodeType::Component(DynComponent { name, props, slots }),
+        });
+
+        id
+    }
+
+    /// `<slot />` and `<slot:name />` render the `Children` prop with that name in front of an empty text node.
+    fn build_slot(&mut self, root: &mut Root, prop: Ident) -> DefaultKey {
+        let id = self.slots.insert(());
+        let ident = node_ident(id);
+
+        self.creation.extend(quote! {
+            let #ident = ui.node();
+            ui.create_text(#ident, "");
+        });
+
+        let dyn_id = root.dynamic_nodes.len();
+        root.dynamic_nodes.push(DynamicNode {
+            root_id: root.idx,
+            id: dyn_id,
+            path: self.current_path.clone(),
+            node: DynamicNodeType::Slot(prop),
+        });
+
+        id
+    }
+
     fn build_node(&mut self, root: &mut Root, node: &Node, force_dyn: bool) -> Vec<QkNode> {
         match node {
-            Node::Element(el) => vec![QkNode::Static(self.build_element(root, el, force_dyn))],
-            Node::Attribute(_) => todo!(),
+            Node::Element(el) => match (slot_name(&el.name), &el.name) {
+                (Some(prop), _) => vec![QkNode::Static(self.build_slot(root, prop))],
+                (None, NodeName::Path(name)) if is_component(&el.name) => {
+                    let name = name.path.clone();
+                    vec![QkNode::Static(self.build_component(root, el, name))]
+                }
+                _ => vec![QkNode::Static(self.build_element(root, el, force_dyn))],
+            },
+            Node::Attribute(_) => {
+                self.errors
+                    .push(node_error(node, "attributes must be inside an element"));
+                Vec::new()
+            }
             Node::Text(text) => {
                 vec![QkNode::Static(self.build_text(root, text, force_dyn))]
             }
-            Node::Comment(_) => todo!(),
-            Node::Doctype(_) => todo!(),
-            Node::Block(_) => todo!(),
-            Node::Fragment(_) => todo!(),
+            Node::Comment(comment) => {
+                vec![QkNode::Static(self.build_comment(root, comment, force_dyn))]
+            }
+            Node::Doctype(_) => {
+                self.errors.push(node_error(
+                    node,
+                    "<!DOCTYPE> must come before the other nodes of a component",
+                ));
+                Vec::new()
+            }
+            Node::Block(block) => {
+                let region = Region::content(block.value.as_ref());
+                vec![QkNode::Static(self.build_region(root, region))]
+            }
+            Node::Fragment(fragment) => items(&fragment.children, &mut self.errors)
+                .into_iter()
+                .flat_map(|item| self.build_item(root, item, force_dyn))
+                .collect(),
         }
     }
 
@@ -167,14 +710,24 @@ impl Elements {
             children,
         } = element;
 
+        if let Err(err) = schema::check_element(&name.to_string(), self.namespace) {
+            self.errors.push(syn::Error::new_spanned(name, err));
+        }
         let name = name.to_string();
 
         let id = self.slots.insert(());
         let ident = node_ident(id);
 
-        self.creation.extend(quote! {
-            let #ident = ui.node();
-            ui.create_element(#ident, #name);
+        let namespace = element_namespace(&name).or(self.namespace);
+        self.creation.extend(match namespace {
+            Some(namespace) => quote! {
+                let #ident = ui.node();
+                ui.create_element_ns(#ident, #name, #namespace);
+            },
+            None => quote! {
+                let #ident = ui.node();
+                ui.create_element(#ident, #name);
+            },
         });
 
         let mut dyn_attributes = Vec::new();
@@ -182,18 +735,42 @@ impl Elements {
 
         for attr in attributes {
             let Node::Attribute(attr) = attr else {
-                panic!("Only attributes are supported here");
+                self.errors.push(node_error(
+                    attr,
+                    "elements only take key={value} attributes",
+                ));
+                continue;
             };
 
             let NodeAttribute { key, value } = attr;
 
+            let Some(value) = value else {
+                self.errors
+                    .push(syn::Error::new_spanned(key, "attributes need a value"));
+                continue;
+            };
+            let checked = match key.to_string() {
+                event if event.starts_with("on") => schema::check_event(&event),
+                attribute => schema::check_attribute(&name, &attribute, namespace),
+            };
+            if let Err(err) = checked {
+                self.errors.push(syn::Error::new_spanned(key, err));
+            }
             let key = key.to_string();
-            let value = value.as_ref().unwrap().as_ref();
+            let value = value.as_ref();
+            let attribute_namespace = attribute_namespace(&key);
 
             if key.starts_with("on") {
+                let Expr::Closure(closure) = value else {
+                    self.errors.push(syn::Error::new_spanned(
+                        value,
+                        "listeners must be closures, like onclick=|_| ...",
+                    ));
+                    continue;
+                };
                 listeners.push(Listener {
                     key,
-                    value: parse_quote!(#value),
+                    value: closure.clone(),
                     states_used: Default::default(),
                 });
             } else if let Expr::Lit(ExprLit {
@@ -202,20 +779,33 @@ impl Elements {
             }) = &value
             {
                 let str_value = lit_str.value();
-                let value = FormattedText::from_str(&str_value).unwrap();
+                let value = match FormattedText::from_str(&str_value) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        self.errors.push(syn::Error::new(lit_str.span(), err));
+                        continue;
+                    }
+                };
                 if value.is_dynamic() {
                     dyn_attributes.push(DynamicAttribute {
                         key,
+                        namespace: attribute_namespace,
                         value: parse_quote! {#value},
                     });
                 } else {
-                    self.creation.extend(quote! {
-                        ui.set_attribute(#ident, #key, #str_value);
+                    self.creation.extend(match attribute_namespace {
+                        Some(namespace) => quote! {
+                            ui.set_attribute_ns(#ident, #key, #namespace, #str_value);
+                        },
+                        None => quote! {
+                            ui.set_attribute(#ident, #key, #str_value);
+                        },
                     });
                 }
             } else {
                 dyn_attributes.push(DynamicAttribute {
                     key,
+                    namespace: attribute_namespace,
                     value: value.clone(),
                 });
             }
@@ -236,11 +826,16 @@ impl Elements {
         }
 
         let prev_path = self.current_path.clone();
+        // the children of a foreignObject are HTML again
+        let prev_namespace = std::mem::replace(
+            &mut self.namespace,
+            namespace.filter(|_| name != "foreignObject"),
+        );
 
         self.current_path.push(TraverseOperation::FirstChild);
 
-        for child in children {
-            let children = self.build_node(root, child, false);
+        for child in items(children, &mut self.errors) {
+            let children = self.build_item(root, child, false);
             for child in children {
                 self.creation.extend(child.append_children(&ident));
             }
@@ -248,6 +843,58 @@ impl Elements {
         }
 
         self.current_path = prev_path;
+        self.namespace = prev_namespace;
+
+        id
+    }
+
+    fn build_comment(
+        &mut self,
+        root: &mut Root,
+        comment: &NodeComment,
+        force_dyn: bool,
+    ) -> DefaultKey {
+        let id = self.slots.insert(());
+        let ident = node_ident(id);
+
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(text),
+            ..
+        }) = comment.value.as_ref()
+        else {
+            self.errors.push(syn::Error::new_spanned(
+                comment.value.as_ref(),
+                "comments must be string literals",
+            ));
+            return id;
+        };
+        // server rendered HTML marks the start of text nodes with `<!--t-->`, hydration would read this comment as one
+        if text.value() == "t" {
+            self.errors.push(syn::Error::new_spanned(
+                text,
+                "the comment `t` is reserved for the text markers in server rendered HTML",
+            ));
+            return id;
+        }
+        self.creation.extend(quote! {
+            let #ident = ui.node();
+            ui.create_comment(#ident, #text);
+        });
+
+        // a comment at the top level only needs a handle, like an element without dynamic attributes
+        if force_dyn {
+            let dyn_id = root.dynamic_nodes.len();
+            root.dynamic_nodes.push(DynamicNode {
+                root_id: root.idx,
+                id: dyn_id,
+                path: self.current_path.clone(),
+                node: DynamicNodeType::Element(DynElement {
+                    attributes: Vec::new(),
+                    listeners: Vec::new(),
+                    children: Vec::new(),
+                }),
+            });
+        }
 
         id
     }
@@ -263,7 +910,13 @@ impl Elements {
             ..
         }) = &value
         {
-            let value = FormattedText::from_str(&lit_str.value()).unwrap();
+            let value = match FormattedText::from_str(&lit_str.value()) {
+                Ok(value) => value,
+                Err(err) => {
+                    self.errors.push(syn::Error::new(lit_str.span(), err));
+                    return id;
+                }
+            };
             if value.is_dynamic() {
                 let id = root.dynamic_nodes.len();
 
@@ -338,6 +991,31 @@ impl Root {
             .unwrap()
             .ident()
     }
+
+    /// Push the nodes this root renders onto `roots`, reading the nodes and states through `owner`: `self.` in the component or nothing while it is created.
+    pub fn push_roots(&self, owner: &TokenStream, roots: &Ident) -> TokenStream {
+        let name = self.root_ident();
+        // regions and components render their nodes in front of their anchor
+        let root_node = self
+            .dynamic_nodes
+            .iter()
+            .find(|dyn_node| dyn_node.path.is_empty())
+            .unwrap();
+        if let Some(region) = root_node.region_ident() {
+            quote! {
+                #owner #region.borrow().roots(&mut #roots);
+            }
+        } else if let Some(child) = root_node.child_ident() {
+            quote! {
+                #roots.extend(qk::component::ComponentState::roots(&#owner #child));
+                #roots.push(#owner #name);
+            }
+        } else {
+            quote! {
+                #roots.push(#owner #name);
+            }
+        }
+    }
 }
 
 pub enum QkNode {
@@ -363,7 +1041,135 @@ impl QkNode {
     }
 }
 
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// The namespace an element starts, its descendants are created in the same namespace.
+fn element_namespace(tag: &str) -> Option<&'static str> {
+    match tag {
+        "svg" => Some(SVG_NAMESPACE),
+        "math" => Some(MATHML_NAMESPACE),
+        _ => None,
+    }
+}
+
+/// The namespace of a prefixed attribute like `xlink:href`.
+fn attribute_namespace(key: &str) -> Option<&'static str> {
+    const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+    if key == "xmlns" {
+        return Some(XMLNS_NAMESPACE);
+    }
+    match key.split_once(':')?.0 {
+        "xlink" => Some("http://www.w3.org/1999/xlink"),
+        "xml" => Some("http://www.w3.org/XML/1998/namespace"),
+        "xmlns" => Some(XMLNS_NAMESPACE),
+        _ => None,
+    }
+}
+
+/// The body of the memo that updates a top level region.
+///
+/// The memo reads every state used in the region up front, so it runs again when any of them change even if the current branch does not read them.
+fn region_memo(
+    region: &Region,
+    region_ident: &Ident,
+    comp_name: &Ident,
+    states: &Vec<crate::state::State>,
+) -> Expr {
+    let region_rx = rx_ident(region_ident);
+    let handle = handle_ident(region_ident);
+    let cx = BlockContext {
+        states,
+        comp_name,
+        handle: &handle,
+        items: &[],
+    };
+    let update = region.update_region(&cx);
+    let body: Expr = parse_quote! {
+        {
+            let mut __region = #region_rx.borrow_mut();
+            let __region = &mut *__region;
+            #update
+        }
+    };
+
+    let mut visitor = SubscriptionVisitor {
+        states,
+        memos: &[],
+        subscribed: Default::default(),
+        memos_read: Default::default(),
+    };
+    visitor.visit_expr(&body);
+    let read = visitor.subscribed.iter().map(|id| &states[*id].name);
+
+    parse_quote! {
+        {
+            #(let _ = &*#read;)*
+            #body
+        }
+    }
+}
+
+/// The prop a `<slot />` or `<slot:name />` element refers to.
+fn slot_name(name: &NodeName) -> Option<Ident> {
+    let name = name.to_string();
+    let prop = match name.as_str() {
+        "slot" => "children",
+        name => name.strip_prefix("slot:")?,
+    };
+    Some(Ident::new(prop, proc_macro2::Span::call_site()))
+}
+
+fn slot_content<'a>(
+    nodes: impl IntoIterator<Item = &'a Node>,
+    errors: &mut Vec<syn::Error>,
+) -> Region {
+    let mut region = Region::Condition(Condition::always(Elements::new(nodes)));
+    region_errors(&mut region, errors);
+    region
+}
+
+/// Components are named like types, `<TakesProp />` or `<module::TakesProp />`.
+fn is_component(name: &NodeName) -> bool {
+    match name {
+        NodeName::Path(path) => path.path.segments.last().is_some_and(|segment| {
+            segment
+                .ident
+                .to_string()
+                .starts_with(|c: char| c.is_ascii_uppercase())
+        }),
+        _ => false,
+    }
+}
+
 fn node_ident(id: DefaultKey) -> proc_macro2::Ident {
     let id = id.data().as_ffi();
     proc_macro2::Ident::new(&format!("__n_{id}"), proc_macro2::Span::call_site())
 }
+
+#[test]
+fn reports_every_error() {
+    let tokens = quote! {
+        <div class>
+            <for x of {items}></for>
+            <p {attributes}></p>
+            <Card data-x={1} />
+            <!-- "t" -->
+        </div>
+    };
+
+    let err = syn::parse2::<Elements>(tokens).unwrap_err();
+    let messages: Vec<_> = err.into_iter().map(|err| err.to_string()).collect();
+    assert_eq!(messages.len(), 5, "{messages:?}");
+    assert_eq!(messages[0], "attributes need a value");
+    assert_eq!(messages[1], "expected <for pattern in {iterator}>");
+    assert_eq!(messages[2], "elements only take key={value} attributes");
+    assert_eq!(
+        messages[3],
+        "component props must be identifiers like `name={value}`"
+    );
+    assert_eq!(
+        messages[4],
+        "the comment `t` is reserved for the text markers in server rendered HTML"
+    );
+}
@@ -4,6 +4,8 @@ use std::collections::HashSet;
 use syn::parse_quote;
 use syn::{Expr, Type};
 
+use crate::component::Component;
+
 /// State that belongs to a component.
 #[derive(Clone)]
 pub struct State {
@@ -12,6 +14,9 @@ pub struct State {
     pub ty: Type,
     pub expr: Expr,
     pub subscribers: HashSet<usize>,
+    // the states and memos the initial value is computed from
+    pub init_states: Vec<usize>,
+    pub init_memos: Vec<usize>,
 }
 
 impl State {
@@ -30,7 +35,7 @@ impl State {
 
     pub fn tracked(&self) -> TokenStream {
         let name = &self.name;
-        let id = self.id as u8;
+        let id = self.id;
 
         quote! {
             RwTrack {
@@ -40,20 +45,44 @@ impl State {
         }
     }
 
-    pub fn construct(&self) -> syn::Stmt {
+    pub fn construct(&self, component: &Component) -> syn::Stmt {
         let name = &self.name;
         let expr = &self.expr;
 
         let private_name = Ident::new(&format!("__{name}"), name.span());
 
+        if self.init_states.is_empty() && self.init_memos.is_empty() {
+            return parse_quote! {
+                #private_name = #expr;
+            };
+        }
+
+        // the initializer reads the values of the states and memos before it
+        let states = self.init_states.iter().map(|id| {
+            let state = &component.states[*id];
+            let name = &state.name;
+            let private = state.private_name();
+            quote!(let #name = &#private;)
+        });
+        let memos = self.init_memos.iter().filter_map(|id| {
+            let memo = &component.memos[*id];
+            let name = memo.name.as_ref()?;
+            let private = memo.private_name();
+            Some(quote!(let #name = &#private.current;))
+        });
+
         parse_quote! {
-            #private_name = #expr;
+            #private_name = {
+                #(#states)*
+                #(#memos)*
+                #expr
+            };
         }
     }
 
     pub fn construct_tracked(&self, path: Expr) -> TokenStream {
         let name = &self.name;
-        let id = self.id as u8;
+        let id = self.id;
         quote! {
             let mut #name = RwTrack {
                 data: #path,
@@ -62,10 +91,10 @@ impl State {
         }
     }
 
-    pub fn tracked_type(&self) -> TokenStream {
+    pub fn tracked_type(&self, tracking: &TokenStream) -> TokenStream {
         let ty = &self.ty;
         quote! {
-            RwTrack<#ty, u8, u8>,
+            RwTrack<#ty, #tracking, #tracking>,
         }
     }
 
@@ -75,36 +104,53 @@ impl State {
         Ident::new(&format!("update_{name}"), name.span())
     }
 
-    pub fn update(&self) -> TokenStream {
+    /// The function that returns the memos a write to this state makes dirty.
+    pub fn dirty_fn(&self) -> Ident {
+        let name = &self.name;
+
+        Ident::new(&format!("dirty_{name}"), name.span())
+    }
+
+    pub fn update(&self, component: &Component) -> TokenStream {
         let name = &self.name;
         let id = self.id;
-        let id_bits = (1u32 << id) as u8;
+        let tracking = component.tracking_ty();
         let update_fn_name = self.update_fn();
-        let maybe_subscribes = self.subscribers.iter().map(|id| {
-            let ident = Ident::new(&format!("memo_{id}",), name.span());
-            let ident_update = Ident::new(&format!("update_{ident}",), name.span());
+        let dirty_fn_name = self.dirty_fn();
+        // memos that read the state the last time they ran
+        let maybe_subscribes = self.subscribers.iter().map(|memo_id| {
+            let is_subscribed = component.memos[*memo_id].is_subscribed(id);
+            let bit = component.memo_bit(*memo_id);
             quote! {
-                if self.#ident.rx_subscriptions & #id_bits != 0{
-                    self.#ident_update();
+                if #is_subscribed {
+                    dirty = qk::prelude::Bits::union(dirty, qk::prelude::Bits::bit(#bit));
                 }
             }
         });
 
         let with_fn_name = Ident::new(&format!("with_{name}"), proc_macro2::Span::call_site());
-        let ty = &self.tracked_type();
+        let ty = &self.tracked_type(&tracking);
 
         quote! {
-            fn #update_fn_name(&mut self) {
-                if self.tracking.get_write() & #id_bits != 0 {
+            #[allow(unused_mut)]
+            fn #dirty_fn_name(&self) -> #tracking {
+                let mut dirty: #tracking = Default::default();
+                if self.tracking.is_write(#id) {
                     #(#maybe_subscribes)*
                 }
+                dirty
+            }
+
+            fn #update_fn_name(&mut self) {
+                let dirty = self.#dirty_fn_name();
+                self.run_memos(dirty);
             }
 
             fn #with_fn_name(&mut self, f: impl FnOnce(#ty)) {
                 self.tracking.reset_write();
                 f(RwTrack {
                     data: &mut self.#name,
-                    tracking: self.tracking.track(#id as u8),
+                    tracking: self.tracking.track(#id),
                 });
                 self.#update_fn_name();
             }
@@ -126,6 +172,8 @@ impl std::fmt::Debug for State {
                 quote!(#expr).to_string()
             })
             .field("subscribers", &self.subscribers)
+            .field("init_states", &self.init_states)
+            .field("init_memos", &self.init_memos)
             .finish()
     }
 }
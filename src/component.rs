@@ -1,5 +1,6 @@
 use std::{cell::RefCell, rc::Rc};
 
+use crate::fragment::Region;
 use crate::prelude::{PlatformEvents, Renderer};
 
 pub trait Component<R, P>
@@ -66,3 +67,155 @@ where
         self.inner.roots()
     }
 }
+
+/// Content a component receives from its caller, either the children of the component or a named slot like `<slot:header>`.
+///
+/// The caller creates the nodes and keeps them up to date with its own state, the component decides where they go by placing `<slot />` or `<slot:header />` in its rsx.
+#[derive(Clone, Debug)]
+pub struct Children {
+    region: Rc<RefCell<Region>>,
+}
+
+impl Children {
+    pub fn new<R: Renderer<P>, P: PlatformEvents>(ui: &mut R) -> Self {
+        let anchor = ui.node();
+        ui.create_text(anchor, "");
+        Self {
+            region: Rc::new(RefCell::new(Region::new(anchor, false))),
+        }
+    }
+
+    /// The region the caller renders the content into.
+    pub fn region(&self) -> &Rc<RefCell<Region>> {
+        &self.region
+    }
+
+    pub fn anchor(&self) -> u32 {
+        self.region.borrow().anchor()
+    }
+
+    /// Move the content in front of `anchor`, which is already part of a tree.
+    pub fn insert_before<R: Renderer<P>, P: PlatformEvents>(&self, ui: &mut R, anchor: u32) {
+        let mut region = self.region.borrow_mut();
+        let mut roots = Vec::new();
+        region.roots(&mut roots);
+        for root in roots {
+            ui.insert_before(anchor, root);
+        }
+        region.mount();
+    }
+}
+
+impl<R, P> ComponentState<R, P> for Children
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+{
+    fn roots(&self) -> Vec<u32> {
+        let mut roots = Vec::new();
+        self.region.borrow().roots(&mut roots);
+        roots
+    }
+}
+
+#[test]
+fn nested_components() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Counter(cx: Scope, start: i32) {
+        let count: Rx<i32> = start;
+
+        rsx! {
+            <button onclick=|_| *count += 1>"{count}"</button>
+        }
+    }
+
+    #[component]
+    fn App(cx: Scope) {
+        let show: Rx<bool> = false;
+
+        rsx! {
+            <Counter start=1 />
+            <div>
+                <Counter start={10 * 2} />
+                <if {*show}>
+                    <Counter start=3 />
+                </if>
+            </div>
+            <p onclick=|_| *show = !*show>"toggle"</p>
+        }
+    }
+
+    let ui = TestRenderer::launch(App {});
+    let root = ui.dom().root();
+    assert_eq!(ui.text(root), "120toggle");
+
+    // each child has its own state
+    ui.click(ui.find("button", 1));
+    ui.click(ui.find("button", 0));
+    assert_eq!(ui.text(root), "221toggle");
+
+    let toggle = ui.find("p", 0);
+    ui.click(toggle);
+    assert_eq!(ui.text(root), "2213toggle");
+    ui.click(ui.find("button", 2));
+    assert_eq!(ui.text(root), "2214toggle");
+
+    ui.click(toggle);
+    assert_eq!(ui.dom().find_all("button").len(), 2);
+}
+
+#[test]
+fn slots() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Card(cx: Scope, header: Children, children: Children) {
+        let open: Rx<bool> = true;
+
+        rsx! {
+            <h1 onclick=|_| *open = !*open>
+                <slot:header />
+            </h1>
+            <if {*open}>
+                <section>
+                    <slot />
+                </section>
+            </if>
+        }
+    }
+
+    #[component]
+    fn App(cx: Scope) {
+        let count: Rx<i32> = 0;
+
+        rsx! {
+            <Card>
+                <slot:header>"count"</slot:header>
+                <button onclick=|_| *count += 1>"{count}"</button>
+            </Card>
+        }
+    }
+
+    let ui = TestRenderer::launch(App {});
+    let root = ui.dom().root();
+    let section = ui.find("section", 0);
+    assert_eq!(ui.text(root), "count0");
+    assert_eq!(ui.text(section), "0");
+
+    // the caller's state updates the content inside the card
+    let button = ui.find("button", 0);
+    ui.click(button);
+    assert_eq!(ui.text(section), "1");
+
+    // the card can hide and show the content again
+    let h1 = ui.find("h1", 0);
+    ui.click(h1);
+    assert_eq!(ui.text(root), "count");
+    ui.click(h1);
+    ui.click(button);
+    assert_eq!(ui.text(root), "count2");
+}
@@ -0,0 +1,300 @@
+use std::borrow::Cow;
+
+use crate::component::{Component, DynComponentState};
+use crate::fragment::{Block, BlockRoot, Child};
+use crate::prelude::{PlatformEvents, Renderer};
+
+/// A value that can be rendered by a `{expr}` child in rsx: text, the props of a component, an `Option` of content or an iterator of content.
+///
+/// `Vec`s, arrays and the common `std::iter` adapters like `map` and `filter` are content. Other iterators, like ranges or `slice::Iter`, are rendered by wrapping them in [`iter`].
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be rendered as content",
+    note = "iterators other than `Vec`s, arrays and the `std::iter` adapters can be rendered with `qk::content::iter({{expr}})`"
+)]
+pub trait Content<R, P>
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+{
+    /// Push the pieces of the content in order.
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>);
+}
+
+/// Creates the state of a component given as content.
+type CreateComponent<R, P> = Box<dyn FnOnce(&mut R) -> DynComponentState<R, P>>;
+
+/// One top level part of some [`Content`], each piece is rendered as its own [`Block`].
+pub enum Piece<R, P>
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+{
+    Text(String),
+    Component(CreateComponent<R, P>),
+}
+
+impl<R, P> Piece<R, P>
+where
+    R: Renderer<P> + 'static,
+    P: PlatformEvents + 'static,
+{
+    pub fn component<C>(props: C) -> Self
+    where
+        C: Component<R, P> + 'static,
+        C::State: 'static,
+    {
+        Piece::Component(Box::new(move |ui| DynComponentState::new(props.create(ui))))
+    }
+
+    /// Create the nodes of the piece. Components are rendered in front of an empty text node like in a template.
+    pub fn create(self, ui: &mut R) -> Block {
+        match self {
+            Piece::Text(text) => {
+                let id = ui.node();
+                ui.create_text(id, &text);
+                Block::new(vec![id], Vec::new(), Vec::new(), vec![BlockRoot::Node(id)])
+            }
+            Piece::Component(create) => {
+                let anchor = ui.node();
+                ui.create_text(anchor, "");
+                let child = Child::new::<R, P, _>(anchor, create(ui));
+                Block::new(
+                    vec![anchor],
+                    Vec::new(),
+                    vec![child],
+                    vec![BlockRoot::Child(0)],
+                )
+            }
+        }
+    }
+}
+
+macro_rules! text_content {
+    ($($t:ty),*) => {
+        $(
+            impl<R: Renderer<P>, P: PlatformEvents> Content<R, P> for $t {
+                fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+                    pieces.push(Piece::Text(self.to_string()));
+                }
+            }
+        )*
+    };
+}
+
+text_content!(
+    String,
+    &str,
+    Cow<'_, str>,
+    char,
+    bool,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
+
+// references to content are rendered by cloning it, which also covers the items of `slice::Iter` and other borrowing iterators
+impl<R, P, T> Content<R, P> for &T
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+    T: Clone + Content<R, P>,
+{
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+        self.clone().pieces(pieces);
+    }
+}
+
+impl<R, P, T> Content<R, P> for Option<T>
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+    T: Content<R, P>,
+{
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+        if let Some(content) = self {
+            content.pieces(pieces);
+        }
+    }
+}
+
+impl<R, P, T> Content<R, P> for Vec<T>
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+    T: Content<R, P>,
+{
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+        for content in self {
+            content.pieces(pieces);
+        }
+    }
+}
+
+impl<R, P, T, const N: usize> Content<R, P> for [T; N]
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+    T: Content<R, P>,
+{
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+        for content in self {
+            content.pieces(pieces);
+        }
+    }
+}
+
+// A blanket impl for every iterator would overlap with the impls for text, so the common iterator types are listed instead and other iterators go through `iter`
+macro_rules! iterator_content {
+    ($($iter:ident<$($param:ident),*> $(where $($bound:ty: $trait:path),*)?;)*) => {
+        $(
+            impl<R, P, $($param),*> Content<R, P> for std::iter::$iter<$($param),*>
+            where
+                R: Renderer<P>,
+                P: PlatformEvents,
+                Self: Iterator,
+                <Self as Iterator>::Item: Content<R, P>,
+                $($($bound: $trait,)*)?
+            {
+                fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+                    for content in self {
+                        content.pieces(pieces);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+iterator_content! {
+    Map<I, F>;
+    Filter<I, F>;
+    FilterMap<I, F>;
+    FlatMap<I, U, F> where U: IntoIterator;
+    Flatten<I> where I: Iterator, I::Item: IntoIterator;
+    Chain<A, B>;
+    Take<I>;
+    Skip<I>;
+    Rev<I>;
+    Cloned<I>;
+    Copied<I>;
+    Once<T>;
+}
+
+impl<R, P, T> Content<R, P> for std::vec::IntoIter<T>
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+    T: Content<R, P>,
+{
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+        for content in self {
+            content.pieces(pieces);
+        }
+    }
+}
+
+/// Content that renders every item of an iterator, created by [`iter`].
+pub struct Iter<I>(I);
+
+/// Render every item of any iterator, like `{iter(0..*count)}` or `{iter(names.iter())}`.
+pub fn iter<I: IntoIterator>(iter: I) -> Iter<I> {
+    Iter(iter)
+}
+
+impl<R, P, I> Content<R, P> for Iter<I>
+where
+    R: Renderer<P>,
+    P: PlatformEvents,
+    I: IntoIterator,
+    I::Item: Content<R, P>,
+{
+    fn pieces(self, pieces: &mut Vec<Piece<R, P>>) {
+        for content in self.0 {
+            content.pieces(pieces);
+        }
+    }
+}
+
+#[test]
+fn dynamic_children() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Label(cx: Scope, text: &'static str) {
+        rsx! {
+            <b>"{text}"</b>
+        }
+    }
+
+    #[component]
+    fn App(cx: Scope) {
+        let count: Rx<usize> = 1;
+
+        rsx! {
+            <div>
+                {*count * 10}
+                {count.is_multiple_of(2).then_some("even")}
+                {(0..*count).map(|_| Label { text: "x" })}
+            </div>
+            <button onclick=|_| *count += 1>"add"</button>
+        }
+    }
+
+    let ui = TestRenderer::launch(App {});
+    let div = ui.find("div", 0);
+    assert_eq!(ui.text(div), "10x");
+
+    let button = ui.find("button", 0);
+    let text = ui.dom().get(div).children()[0];
+    ui.click(button);
+    assert_eq!(ui.text(div), "20evenxx");
+    // text that stays text is updated in place
+    assert_eq!(ui.dom().get(div).children()[0], text);
+
+    ui.click(button);
+    assert_eq!(ui.text(div), "30xxx");
+    assert_eq!(ui.dom().find_all("b").len(), 3);
+}
+
+#[test]
+fn iterator_children() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+    use std::collections::BTreeMap;
+
+    #[component]
+    fn App(cx: Scope) {
+        let names: Rx<Vec<String>> = vec!["a".to_string(), "b".to_string()];
+        let ages: Rx<BTreeMap<&'static str, u32>> = BTreeMap::from([("x", 1), ("y", 2)]);
+
+        rsx! {
+            <p>{iter(0..names.len())}</p>
+            <p>{iter(names.iter())}</p>
+            <p>{iter(ages.values().step_by(2))}</p>
+            <button onclick=|_| names.push("c".to_string())>"add"</button>
+        }
+    }
+
+    let ui = TestRenderer::launch(App {});
+    let text = |ui: &TestRenderer| {
+        (0..3)
+            .map(|idx| ui.text(ui.find("p", idx)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(text(&ui), ["01", "ab", "1"]);
+
+    ui.click(ui.find("button", 0));
+    assert_eq!(text(&ui), ["012", "abc", "1"]);
+}
@@ -0,0 +1,1277 @@
+use std::any::TypeId;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::rc::Rc;
+
+use crate::events::{EventDescription, PlatformEvents};
+use crate::renderer::{Hydrate, HydrationError, Renderer};
+use crate::slab::IdSlab;
+
+/// Elements that never have children or a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Marks the start of a text node in server rendered HTML.
+///
+/// `rsx!` rejects the comment `t`, so no comment a component renders is read as a marker.
+const TEXT_MARKER: &str = "<!--t-->";
+
+/// A handle to a node in a [`Dom`].
+///
+/// Unlike the `u32` ids handed out by [`crate::renderer::Renderer::node`], a `NodeId` refers to the same node until the node is freed, see [`Dom`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeKind {
+    Element {
+        tag: String,
+        /// The namespace of elements like `svg` that are not HTML elements.
+        namespace: Option<String>,
+        attributes: Vec<Attribute>,
+        styles: Vec<(String, String)>,
+    },
+    Text(String),
+    Comment(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    /// The namespace of attributes like `xlink:href` that are set with [`crate::renderer::Renderer::set_attribute_ns`].
+    pub namespace: Option<String>,
+    pub value: String,
+}
+
+impl Attribute {
+    fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            namespace: None,
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DomNode {
+    kind: NodeKind,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    // the number of ids that point at the node
+    ids: usize,
+}
+
+impl DomNode {
+    pub fn kind(&self) -> &NodeKind {
+        &self.kind
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        match &self.kind {
+            NodeKind::Element { tag, .. } => Some(tag),
+            NodeKind::Text(_) | NodeKind::Comment(_) => None,
+        }
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        match &self.kind {
+            NodeKind::Element { namespace, .. } => namespace.as_deref(),
+            NodeKind::Text(_) | NodeKind::Comment(_) => None,
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.find_attribute(name)
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    /// The namespace the attribute was set in, if it was set with a namespace.
+    pub fn attribute_namespace(&self, name: &str) -> Option<&str> {
+        self.find_attribute(name)?.namespace.as_deref()
+    }
+
+    fn find_attribute(&self, name: &str) -> Option<&Attribute> {
+        match &self.kind {
+            NodeKind::Element { attributes, .. } => {
+                attributes.iter().find(|attribute| attribute.name == name)
+            }
+            NodeKind::Text(_) | NodeKind::Comment(_) => None,
+        }
+    }
+
+    pub fn style(&self, name: &str) -> Option<&str> {
+        match &self.kind {
+            NodeKind::Element { styles, .. } => styles
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str()),
+            NodeKind::Text(_) | NodeKind::Comment(_) => None,
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match &self.kind {
+            NodeKind::Text(text) => Some(text),
+            NodeKind::Element { .. } | NodeKind::Comment(_) => None,
+        }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// An in-memory tree that applies renderer operations the same way the JS in [`crate::web`] applies them to the browser DOM.
+///
+/// Every `u32` id points at a node (or nothing, after `first_child`/`next_sibling` walks off the end) and several ids may point at the same node after `copy`.
+/// Once an id is returned, a node that is not in the tree is freed along with its descendants if no other id points into them, and its `NodeId` is reused for a later node.
+pub struct Dom {
+    nodes: Vec<DomNode>,
+    // the nodes that were freed and can be reused
+    free: Vec<NodeId>,
+    // the nodes to free once the backend saw them, see [`Dom::take_garbage`]
+    garbage: Vec<NodeId>,
+    ids: IdSlab<Option<NodeId>>,
+    // the stand-ins while hydrating, by their node
+    hydration: Option<HashMap<NodeId, StandIn>>,
+}
+
+/// A node that stands in for a server rendered node while hydrating, see [`Dom::start_hydration`].
+struct StandIn {
+    // the node of the template it stands in for
+    template: NodeId,
+    // the stand-in of the parent and the index in it, for nodes found with `first_child` and `next_sibling`
+    parent: Option<(NodeId, usize)>,
+    children: HashMap<usize, NodeId>,
+    // the nodes inserted in front of it
+    before: Vec<NodeId>,
+}
+
+impl Default for Dom {
+    fn default() -> Self {
+        let mut myself = Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            garbage: Vec::new(),
+            ids: IdSlab::default(),
+            hydration: None,
+        };
+
+        // the root node
+        let root = myself.insert(NodeKind::Element {
+            tag: "div".to_string(),
+            namespace: None,
+            attributes: vec![Attribute::new("id", "main")],
+            styles: Vec::new(),
+        });
+        myself.ids.id(Some(root));
+        myself.nodes[root.0].ids += 1;
+
+        myself
+    }
+}
+
+impl Dom {
+    /// The node with the id `0` that every component is mounted into.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn get(&self, node: NodeId) -> &DomNode {
+        &self.nodes[node.0]
+    }
+
+    /// Find the node a renderer id currently points at.
+    pub fn lookup(&self, id: u32) -> Option<NodeId> {
+        self.ids.get(id).copied().flatten()
+    }
+
+    /// The concatenated text of a node and all of its descendants.
+    pub fn text_content(&self, node: NodeId) -> String {
+        let mut text = String::new();
+        self.collect_text(node, &mut text);
+        text
+    }
+
+    fn collect_text(&self, node: NodeId, text: &mut String) {
+        let node = self.get(node);
+        match &node.kind {
+            NodeKind::Text(value) => text.push_str(value),
+            NodeKind::Comment(_) => {}
+            NodeKind::Element { .. } => {
+                for child in &node.children {
+                    self.collect_text(*child, text);
+                }
+            }
+        }
+    }
+
+    /// All elements with the given tag that are attached to the root, in document order.
+    pub fn find_all(&self, tag: &str) -> Vec<NodeId> {
+        let mut found = Vec::new();
+        self.collect_tag(self.root(), tag, &mut found);
+        found
+    }
+
+    fn collect_tag(&self, node: NodeId, tag: &str, found: &mut Vec<NodeId>) {
+        for child in self.get(node).children() {
+            if self.get(*child).tag() == Some(tag) {
+                found.push(*child);
+            }
+            self.collect_tag(*child, tag, found);
+        }
+    }
+
+    /// Serialize the children of a node as HTML.
+    pub fn inner_html(&self, node: NodeId) -> String {
+        let mut html = String::new();
+        self.write_children(node, &mut html)
+            .expect("writing to a string cannot fail");
+        html
+    }
+
+    /// Serialize the children of a node as HTML.
+    ///
+    /// The browser merges adjacent text nodes and drops empty ones when it parses HTML, so text nodes that would be lost are preceded by a `<!--t-->` marker that hydration turns back into a text node boundary.
+    pub fn write_children(&self, node: NodeId, out: &mut impl Write) -> fmt::Result {
+        let mut after_text = false;
+        for child in self.get(node).children() {
+            self.write_sibling(*child, &mut after_text, out)?;
+        }
+        Ok(())
+    }
+
+    /// Serialize one node of a list of siblings, adding a text marker if the previous sibling was text.
+    pub(crate) fn write_sibling(
+        &self,
+        node: NodeId,
+        after_text: &mut bool,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        if let NodeKind::Text(text) = &self.get(node).kind {
+            if *after_text || text.is_empty() {
+                out.write_str(TEXT_MARKER)?;
+            }
+            *after_text = true;
+        } else {
+            *after_text = false;
+        }
+        self.write_html(node, out)
+    }
+
+    /// Serialize a node and its descendants as HTML.
+    pub fn write_html(&self, node: NodeId, out: &mut impl Write) -> fmt::Result {
+        match &self.get(node).kind {
+            NodeKind::Text(text) => escape_text(text, out),
+            NodeKind::Comment(text) => {
+                out.write_str("<!--")?;
+                // a comment ends at the first `-->`
+                out.write_str(&text.replace("-->", "--&gt;"))?;
+                out.write_str("-->")
+            }
+            NodeKind::Element {
+                tag,
+                namespace,
+                attributes,
+                styles,
+            } => {
+                write!(out, "<{tag}")?;
+                for Attribute { name, value, .. } in attributes {
+                    write!(out, " {name}=\"")?;
+                    escape_attribute(value, out)?;
+                    out.write_char('"')?;
+                }
+                if !styles.is_empty() {
+                    out.write_str(" style=\"")?;
+                    for (name, value) in styles {
+                        write_css_name(name, out)?;
+                        out.write_char(':')?;
+                        escape_attribute(value, out)?;
+                        out.write_char(';')?;
+                    }
+                    out.write_char('"')?;
+                }
+                out.write_char('>')?;
+                if namespace.is_none() && VOID_ELEMENTS.contains(&tag.as_str()) {
+                    return Ok(());
+                }
+                self.write_children(node, out)?;
+                write!(out, "</{tag}>")
+            }
+        }
+    }
+
+    fn insert(&mut self, kind: NodeKind) -> NodeId {
+        let node = DomNode {
+            kind,
+            parent: None,
+            children: Vec::new(),
+            ids: 0,
+        };
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id.0] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                NodeId(self.nodes.len() - 1)
+            }
+        }
+    }
+
+    fn node_mut(&mut self, id: u32) -> &mut DomNode {
+        let node = self.expect(id);
+        &mut self.nodes[node.0]
+    }
+
+    fn expect(&self, id: u32) -> NodeId {
+        self.lookup(id)
+            .unwrap_or_else(|| panic!("node {id} does not point at a node"))
+    }
+
+    fn set(&mut self, id: u32, node: Option<NodeId>) {
+        let old = std::mem::replace(self.ids.get_mut(id).expect("node id was returned"), node);
+        if let Some(old) = old {
+            self.nodes[old.0].ids -= 1;
+        }
+        if let Some(node) = node {
+            self.nodes[node.0].ids += 1;
+        }
+    }
+
+    pub(crate) fn node(&mut self) -> u32 {
+        self.ids.id(None)
+    }
+
+    pub(crate) fn return_node(&mut self, id: u32) {
+        let node = self.lookup(id);
+        self.set(id, None);
+        self.ids.recycle(id);
+        if let Some(node) = node {
+            self.collect(node);
+        }
+    }
+
+    /// Queue the detached tree `node` is in to be freed if no id points into it.
+    fn collect(&mut self, mut node: NodeId) {
+        // stand-ins are detached until they are matched
+        if self.hydration.is_some() {
+            return;
+        }
+        while let Some(parent) = self.nodes[node.0].parent {
+            node = parent;
+        }
+        if node == self.root() {
+            return;
+        }
+        let mut subtree = Vec::new();
+        self.collect_subtree(node, &mut subtree);
+        if subtree.iter().all(|node| self.nodes[node.0].ids == 0) {
+            self.garbage.extend(subtree);
+        }
+    }
+
+    fn collect_subtree(&self, node: NodeId, subtree: &mut Vec<NodeId>) {
+        subtree.push(node);
+        for child in &self.nodes[node.0].children {
+            self.collect_subtree(*child, subtree);
+        }
+    }
+
+    /// The nodes that left the tree for good since the last call. They stay readable until they are passed to [`Dom::free`].
+    pub(crate) fn take_garbage(&mut self) -> Vec<NodeId> {
+        std::mem::take(&mut self.garbage)
+    }
+
+    pub(crate) fn free(&mut self, nodes: &[NodeId]) {
+        for node in nodes {
+            let node = &mut self.nodes[node.0];
+            node.kind = NodeKind::Comment(String::new());
+            node.parent = None;
+            node.children.clear();
+        }
+        // reuse them in order, so a clone of the same tree takes the same nodes
+        self.free.extend(nodes.iter().rev());
+    }
+
+    pub(crate) fn create_element(&mut self, id: u32, tag: &str) {
+        let node = self.insert(NodeKind::Element {
+            tag: tag.to_string(),
+            namespace: None,
+            attributes: Vec::new(),
+            styles: Vec::new(),
+        });
+        self.set(id, Some(node));
+    }
+
+    pub(crate) fn create_element_ns(&mut self, id: u32, tag: &str, namespace: &str) {
+        let node = self.insert(NodeKind::Element {
+            tag: tag.to_string(),
+            namespace: Some(namespace.to_string()),
+            attributes: Vec::new(),
+            styles: Vec::new(),
+        });
+        self.set(id, Some(node));
+    }
+
+    pub(crate) fn create_text(&mut self, id: u32, text: &str) {
+        let node = self.insert(NodeKind::Text(text.to_string()));
+        self.set(id, Some(node));
+    }
+
+    pub(crate) fn create_comment(&mut self, id: u32, text: &str) {
+        let node = self.insert(NodeKind::Comment(text.to_string()));
+        self.set(id, Some(node));
+    }
+
+    pub(crate) fn set_attribute(&mut self, id: u32, name: &str, value: &str) {
+        let node = self.expect(id);
+        self.set_node_attribute(node, name, value);
+    }
+
+    pub(crate) fn set_attribute_ns(&mut self, id: u32, name: &str, namespace: &str, value: &str) {
+        let node = self.expect(id);
+        self.set_node_attribute(node, name, value);
+        if let NodeKind::Element { attributes, .. } = &mut self.nodes[node.0].kind {
+            if let Some(attribute) = attributes
+                .iter_mut()
+                .find(|attribute| attribute.name == name)
+            {
+                attribute.namespace = Some(namespace.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn set_node_attribute(&mut self, node: NodeId, name: &str, value: &str) {
+        if let NodeKind::Element { attributes, .. } = &mut self.nodes[node.0].kind {
+            match attributes
+                .iter_mut()
+                .find(|attribute| attribute.name == name)
+            {
+                Some(attribute) => attribute.value = value.to_string(),
+                None => attributes.push(Attribute::new(name, value)),
+            }
+        }
+    }
+
+    pub(crate) fn remove_attribute(&mut self, id: u32, name: &str) {
+        if let NodeKind::Element { attributes, .. } = &mut self.node_mut(id).kind {
+            attributes.retain(|attribute| attribute.name != name);
+        }
+    }
+
+    pub(crate) fn set_style(&mut self, id: u32, name: &str, value: &str) {
+        if let NodeKind::Element { styles, .. } = &mut self.node_mut(id).kind {
+            match styles.iter_mut().find(|(key, _)| key == name) {
+                Some((_, old)) => *old = value.to_string(),
+                None => styles.push((name.to_string(), value.to_string())),
+            }
+        }
+    }
+
+    pub(crate) fn set_text(&mut self, id: u32, text: &str) {
+        let node = self.expect(id);
+        match &mut self.nodes[node.0].kind {
+            NodeKind::Text(old) | NodeKind::Comment(old) => *old = text.to_string(),
+            NodeKind::Element { .. } => {
+                // textContent replaces every child with a single text node
+                for child in std::mem::take(&mut self.nodes[node.0].children) {
+                    self.nodes[child.0].parent = None;
+                    self.collect(child);
+                }
+                if !text.is_empty() {
+                    let child = self.insert(NodeKind::Text(text.to_string()));
+                    self.attach(node, child);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn append_child(&mut self, parent: u32, child: u32) {
+        let parent = self.expect(parent);
+        let child = self.expect(child);
+        self.attach(parent, child);
+    }
+
+    pub(crate) fn insert_before(&mut self, anchor: u32, id: u32) {
+        let anchor = self.expect(anchor);
+        let node = self.expect(id);
+        if let Some(stand_in) = self
+            .hydration
+            .as_mut()
+            .and_then(|stand_ins| stand_ins.get_mut(&anchor))
+        {
+            stand_in.before.push(node);
+            return;
+        }
+        self.detach(node);
+        let parent = self.nodes[anchor.0]
+            .parent
+            .expect("inserted before a node without a parent");
+        let siblings = &mut self.nodes[parent.0].children;
+        let idx = siblings
+            .iter()
+            .position(|sibling| *sibling == anchor)
+            .unwrap();
+        siblings.insert(idx, node);
+        self.nodes[node.0].parent = Some(parent);
+    }
+
+    fn attach(&mut self, parent: NodeId, child: NodeId) {
+        self.detach(child);
+        self.nodes[child.0].parent = Some(parent);
+        self.nodes[parent.0].children.push(child);
+    }
+
+    fn detach(&mut self, node: NodeId) {
+        if let Some(parent) = self.nodes[node.0].parent.take() {
+            self.nodes[parent.0].children.retain(|child| *child != node);
+        }
+    }
+
+    pub(crate) fn clone_node(&mut self, id: u32, new_id: u32) {
+        let node = self.expect(id);
+        let cloned = if self.hydration.is_some() {
+            self.stand_in(node, None)
+        } else {
+            self.deep_clone(node)
+        };
+        self.set(new_id, Some(cloned));
+    }
+
+    fn deep_clone(&mut self, node: NodeId) -> NodeId {
+        let kind = self.nodes[node.0].kind.clone();
+        let cloned = self.insert(kind);
+        for child in self.nodes[node.0].children.clone() {
+            let child = self.deep_clone(child);
+            self.attach(cloned, child);
+        }
+        cloned
+    }
+
+    pub(crate) fn copy(&mut self, from: u32, to: u32) {
+        let node = self.lookup(from);
+        self.set(to, node);
+    }
+
+    pub(crate) fn first_child(&mut self, id: u32) {
+        let node = self.expect(id);
+        let child = if self.is_stand_in(node) {
+            self.stand_in_child(node, 0)
+        } else {
+            self.nodes[node.0].children.first().copied()
+        };
+        self.set(id, child);
+    }
+
+    pub(crate) fn next_sibling(&mut self, id: u32) {
+        let node = self.expect(id);
+        if let Some(stand_ins) = &self.hydration {
+            if let Some(stand_in) = stand_ins.get(&node) {
+                let sibling = stand_in
+                    .parent
+                    .and_then(|(parent, idx)| self.stand_in_child(parent, idx + 1));
+                self.set(id, sibling);
+                return;
+            }
+        }
+        let sibling = self.nodes[node.0].parent.and_then(|parent| {
+            let siblings = &self.nodes[parent.0].children;
+            let idx = siblings.iter().position(|sibling| *sibling == node)?;
+            siblings.get(idx + 1).copied()
+        });
+        self.set(id, sibling);
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) {
+        let node = self.expect(id);
+        self.detach(node);
+    }
+
+    /// Make template clones stand-ins for server rendered nodes until [`Dom::finish_hydration`], like the web renderer does when it hydrates.
+    ///
+    /// A stand-in is a node without children that receives the attributes and listeners of the node it stands in for. `first_child` and `next_sibling` on it find stand-ins for the nodes of the template and `insert_before` it only records the inserted node.
+    pub(crate) fn start_hydration(&mut self) {
+        self.hydration = Some(HashMap::new());
+    }
+
+    fn stand_in(&mut self, template: NodeId, parent: Option<(NodeId, usize)>) -> NodeId {
+        let node = self.insert(self.nodes[template.0].kind.clone());
+        let stand_in = StandIn {
+            template,
+            parent,
+            children: HashMap::new(),
+            before: Vec::new(),
+        };
+        self.hydration
+            .as_mut()
+            .expect("not hydrating")
+            .insert(node, stand_in);
+        node
+    }
+
+    fn stand_in_child(&mut self, parent: NodeId, idx: usize) -> Option<NodeId> {
+        let stand_ins = self.hydration.as_ref()?;
+        let stand_in = &stand_ins[&parent];
+        if let Some(child) = stand_in.children.get(&idx) {
+            return Some(*child);
+        }
+        let template = *self.nodes[stand_in.template.0].children.get(idx)?;
+        let child = self.stand_in(template, Some((parent, idx)));
+        self.hydration
+            .as_mut()
+            .and_then(|stand_ins| stand_ins.get_mut(&parent))
+            .expect("not hydrating")
+            .children
+            .insert(idx, child);
+        Some(child)
+    }
+
+    fn is_stand_in(&self, node: NodeId) -> bool {
+        self.hydration
+            .as_ref()
+            .is_some_and(|stand_ins| stand_ins.contains_key(&node))
+    }
+
+    /// Match the stand-ins with the children of the root, starting from the nodes of `roots`, and point ids at the server rendered nodes. Returns the server rendered node each node was swapped for.
+    ///
+    /// Nothing is changed if a node does not match.
+    pub(crate) fn finish_hydration(
+        &mut self,
+        roots: &[u32],
+    ) -> Result<HashMap<NodeId, NodeId>, HydrationError> {
+        let stand_ins = self.hydration.take().expect("not hydrating");
+        let roots: Vec<_> = roots
+            .iter()
+            .map(|root| (self.expect(*root), true))
+            .collect();
+        let mut matched = Vec::new();
+        self.match_children(&stand_ins, &roots, self.root(), &mut matched)?;
+
+        let mut adopted = HashMap::new();
+        for (client, server) in matched {
+            // delegated listeners are attributes the server did not render
+            if let NodeKind::Element { attributes, .. } = &self.nodes[client.0].kind {
+                let listeners: Vec<_> = attributes
+                    .iter()
+                    .filter(|attribute| attribute.name.starts_with("data-event-"))
+                    .cloned()
+                    .collect();
+                for Attribute { name, value, .. } in listeners {
+                    self.set_node_attribute(server, &name, &value);
+                }
+            }
+            adopted.insert(client, server);
+        }
+        for node in self.ids.iter_mut().flatten() {
+            if let Some(server) = adopted.get(node) {
+                self.nodes[node.0].ids -= 1;
+                self.nodes[server.0].ids += 1;
+                *node = *server;
+            }
+        }
+        // the stand-ins and the nodes the server rendered nodes replaced are no longer used
+        let mut unused: Vec<_> = (stand_ins.keys().chain(adopted.keys()))
+            .copied()
+            .filter(|node| self.nodes[node.0].ids == 0)
+            .collect();
+        unused.sort_by_key(|node| node.0);
+        unused.dedup();
+        self.free(&unused);
+        Ok(adopted)
+    }
+
+    /// Match `children`, and the nodes inserted in front of them, with the children of `server`. Only the nodes marked to be recorded are pushed to `matched`, the static nodes of a template stay in the template.
+    fn match_children(
+        &self,
+        stand_ins: &HashMap<NodeId, StandIn>,
+        children: &[(NodeId, bool)],
+        server: NodeId,
+        matched: &mut Vec<(NodeId, NodeId)>,
+    ) -> Result<(), HydrationError> {
+        let server_children = &self.nodes[server.0].children;
+        let mut server_children = server_children.iter().copied().enumerate();
+        let mut next = || {
+            server_children
+                .next()
+                .map(|(_, child)| child)
+                .ok_or_else(|| self.mismatch(server, self.nodes[server.0].children.len()))
+        };
+        for (child, record) in children {
+            if let Some(stand_in) = stand_ins.get(child) {
+                for before in &stand_in.before {
+                    self.match_node(stand_ins, *before, next()?, true, matched)?;
+                }
+            }
+            self.match_node(stand_ins, *child, next()?, *record, matched)?;
+        }
+        match server_children.next() {
+            Some((idx, _)) => Err(self.mismatch(server, idx)),
+            None => Ok(()),
+        }
+    }
+
+    fn match_node(
+        &self,
+        stand_ins: &HashMap<NodeId, StandIn>,
+        client: NodeId,
+        server: NodeId,
+        record: bool,
+        matched: &mut Vec<(NodeId, NodeId)>,
+    ) -> Result<(), HydrationError> {
+        if !self.same_kind(client, server) {
+            return Err(self.mismatch_at(server));
+        }
+        if record {
+            matched.push((client, server));
+        }
+        let children: Vec<_> = match stand_ins.get(&client) {
+            // the nodes of the template that were never looked at have no stand-in
+            Some(stand_in) => (self.nodes[stand_in.template.0].children.iter())
+                .enumerate()
+                .map(|(idx, template)| match stand_in.children.get(&idx) {
+                    Some(child) => (*child, true),
+                    None => (*template, false),
+                })
+                .collect(),
+            None => (self.nodes[client.0].children.iter())
+                .map(|child| (*child, record))
+                .collect(),
+        };
+        self.match_children(stand_ins, &children, server, matched)
+    }
+
+    fn same_kind(&self, client: NodeId, server: NodeId) -> bool {
+        match (&self.nodes[client.0].kind, &self.nodes[server.0].kind) {
+            (NodeKind::Element { tag, .. }, NodeKind::Element { tag: other, .. }) => tag == other,
+            (NodeKind::Text(_), NodeKind::Text(_)) => true,
+            (NodeKind::Comment(_), NodeKind::Comment(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// The error for the child `idx` of `parent`, which is missing or should not be there.
+    fn mismatch(&self, parent: NodeId, idx: usize) -> HydrationError {
+        let mut path = self.mismatch_at(parent).path;
+        path.push(idx);
+        HydrationError { path }
+    }
+
+    fn mismatch_at(&self, mut node: NodeId) -> HydrationError {
+        let mut path = Vec::new();
+        while let Some(parent) = self.nodes[node.0].parent {
+            let siblings = &self.nodes[parent.0].children;
+            path.push(
+                siblings
+                    .iter()
+                    .position(|sibling| *sibling == node)
+                    .unwrap(),
+            );
+            node = parent;
+        }
+        path.reverse();
+        HydrationError { path }
+    }
+
+    /// Parse HTML written by [`Dom::write_html`] into the children of `parent` like the browser and the hydration script in [`crate::web`] do: `<!--t-->` markers split text nodes and stand in for empty ones.
+    pub fn append_html(&mut self, parent: NodeId, html: &str) {
+        let mut parents = vec![parent];
+        let mut rest = html;
+        // a marker that is not followed by text is an empty text node
+        let mut marker = false;
+        while !rest.is_empty() {
+            let parent = *parents.last().expect("more closing tags than elements");
+            if let Some(after) = rest.strip_prefix(TEXT_MARKER) {
+                if std::mem::replace(&mut marker, true) {
+                    self.append_node(parent, NodeKind::Text(String::new()));
+                }
+                rest = after;
+                continue;
+            }
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.append_node(parent, NodeKind::Text(unescape(&rest[..end])));
+                marker = false;
+                rest = &rest[end..];
+                continue;
+            }
+            if std::mem::take(&mut marker) {
+                self.append_node(parent, NodeKind::Text(String::new()));
+            }
+            if let Some(after) = rest.strip_prefix("<!--") {
+                let end = after.find("-->").expect("unclosed comment");
+                self.append_node(
+                    parent,
+                    NodeKind::Comment(after[..end].replace("--&gt;", "-->")),
+                );
+                rest = &after[end + 3..];
+            } else if let Some(after) = rest.strip_prefix("</") {
+                parents.pop();
+                rest = &after[after.find('>').expect("unclosed tag") + 1..];
+            } else if rest.starts_with("<!") {
+                // the doctype is not part of the tree
+                rest = &rest[rest.find('>').expect("unclosed doctype") + 1..];
+            } else {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .expect("unclosed tag");
+                let tag = rest[1..end].to_string();
+                rest = &rest[end..];
+                let mut attributes = Vec::new();
+                loop {
+                    rest = rest.trim_start();
+                    if let Some(after) = rest.strip_prefix('>') {
+                        rest = after;
+                        break;
+                    }
+                    let (name, after) = rest.split_once("=\"").expect("attributes are quoted");
+                    let (value, after) = after.split_once('"').expect("unclosed attribute");
+                    attributes.push(Attribute::new(name, &unescape(value)));
+                    rest = after;
+                }
+                let void = VOID_ELEMENTS.contains(&tag.as_str());
+                let element = self.append_node(
+                    parent,
+                    NodeKind::Element {
+                        tag,
+                        namespace: None,
+                        attributes,
+                        styles: Vec::new(),
+                    },
+                );
+                if !void {
+                    parents.push(element);
+                }
+            }
+        }
+        if marker {
+            self.append_node(*parents.last().unwrap(), NodeKind::Text(String::new()));
+        }
+    }
+
+    fn append_node(&mut self, parent: NodeId, kind: NodeKind) -> NodeId {
+        let node = self.insert(kind);
+        self.attach(parent, node);
+        node
+    }
+}
+
+/// What a [`DomRenderer`] does besides applying operations to its [`Dom`]: handling listeners, and writing out the tree if it is a server renderer.
+pub trait DomBackend: Sized {
+    /// The event every listener of the renderer receives.
+    type Event;
+
+    fn add_listener<E: EventDescription<DomRenderer<Self>>>(
+        &mut self,
+        dom: &mut Dom,
+        id: u32,
+        event: E,
+        callback: Box<dyn FnMut(Self::Event)>,
+    );
+
+    /// Called after `child` is appended to the root node.
+    fn append_to_root(&mut self, _dom: &mut Dom, _child: u32) {}
+
+    fn set_doctype(&mut self, _doctype: &str) {}
+
+    fn flush(&mut self) {}
+
+    fn start_component(&mut self) {}
+
+    fn end_component(&mut self) {}
+
+    fn roots_finished(&mut self, _dom: &mut Dom, _roots: &[u32]) {}
+
+    /// Called after hydrating with the server rendered node each node was swapped for.
+    fn hydrated(&mut self, _adopted: &HashMap<NodeId, NodeId>) {}
+
+    /// Called after `node` is removed from its parent.
+    fn removed(&mut self, _dom: &Dom, _node: NodeId) {}
+
+    /// Called before `nodes` are freed, once they left the tree and no id points at them. Their `NodeId`s are reused afterwards.
+    fn freed(&mut self, _dom: &Dom, _nodes: &[NodeId]) {}
+}
+
+/// A renderer that keeps its tree in a [`Dom`], like the test, SSR and TUI renderers.
+///
+/// Clones share the same tree, so a component and the code that launched it see the same nodes.
+pub struct DomRenderer<B>(pub(crate) Rc<RefCell<DomState<B>>>);
+
+pub struct DomState<B> {
+    pub(crate) dom: Dom,
+    templates: HashMap<TypeId, Vec<u32>>,
+    pub(crate) backend: B,
+}
+
+impl<B> DomRenderer<B> {
+    pub fn with_backend(backend: B) -> Self {
+        Self(Rc::new(RefCell::new(DomState {
+            dom: Dom::default(),
+            templates: HashMap::new(),
+            backend,
+        })))
+    }
+
+    /// Borrow the current tree.
+    pub fn dom(&self) -> Ref<'_, Dom> {
+        Ref::map(self.0.borrow(), |myself| &myself.dom)
+    }
+}
+
+impl<B: DomBackend> DomState<B> {
+    fn collect_garbage(&mut self) {
+        let garbage = self.dom.take_garbage();
+        if !garbage.is_empty() {
+            self.backend.freed(&self.dom, &garbage);
+            self.dom.free(&garbage);
+        }
+    }
+}
+
+impl<B> Clone for DomRenderer<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B: Default> Default for DomRenderer<B> {
+    fn default() -> Self {
+        Self::with_backend(B::default())
+    }
+}
+
+impl<B: DomBackend> Hydrate for DomRenderer<B> {
+    fn start_hydration(&mut self) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.start_hydration();
+    }
+
+    fn finish_hydration(&mut self, roots: &[u32]) -> Result<(), HydrationError> {
+        let myself = &mut *self.0.borrow_mut();
+        let adopted = myself.dom.finish_hydration(roots)?;
+        myself.backend.hydrated(&adopted);
+        Ok(())
+    }
+}
+
+impl<B: DomBackend> PlatformEvents for DomRenderer<B> {
+    type AnimationEvent = B::Event;
+    type BeforeUnloadEvent = B::Event;
+    type CompositionEvent = B::Event;
+    type DeviceMotionEvent = B::Event;
+    type DeviceOrientationEvent = B::Event;
+    type DragEvent = B::Event;
+    type ErrorEvent = B::Event;
+    type FocusEvent = B::Event;
+    type GamepadEvent = B::Event;
+    type HashChangeEvent = B::Event;
+    type InputEvent = B::Event;
+    type KeyboardEvent = B::Event;
+    type MessageEvent = B::Event;
+    type MouseEvent = B::Event;
+    type PageTransitionEvent = B::Event;
+    type PointerEvent = B::Event;
+    type PopStateEvent = B::Event;
+    type PromiseRejectionEvent = B::Event;
+    type SecurityPolicyViolationEvent = B::Event;
+    type StorageEvent = B::Event;
+    type SubmitEvent = B::Event;
+    type TouchEvent = B::Event;
+    type TransitionEvent = B::Event;
+    type UiEvent = B::Event;
+    type WheelEvent = B::Event;
+    type ProgressEvent = B::Event;
+    type Event = B::Event;
+}
+
+impl<B: DomBackend> Renderer<DomRenderer<B>> for DomRenderer<B> {
+    fn node(&mut self) -> u32 {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.node()
+    }
+
+    fn append_all(&mut self, parent: u32, children: impl IntoIterator<Item = u32>) {
+        for child in children.into_iter() {
+            self.append_child(parent, child);
+        }
+    }
+
+    fn set_attribute(&mut self, id: u32, name: &'static str, value: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.set_attribute(id, name, value);
+    }
+
+    fn set_attribute_ns(
+        &mut self,
+        id: u32,
+        name: &'static str,
+        namespace: &'static str,
+        value: &str,
+    ) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.set_attribute_ns(id, name, namespace, value);
+    }
+
+    fn remove_attribute(&mut self, id: u32, name: &'static str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.remove_attribute(id, name);
+    }
+
+    fn set_style(&mut self, id: u32, name: &'static str, value: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.set_style(id, name, value);
+    }
+
+    fn create_element(&mut self, id: u32, tag: &'static str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.create_element(id, tag);
+    }
+
+    fn create_element_ns(&mut self, id: u32, tag: &'static str, namespace: &'static str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.create_element_ns(id, tag, namespace);
+    }
+
+    fn create_text(&mut self, id: u32, text: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.create_text(id, text);
+    }
+
+    fn create_comment(&mut self, id: u32, text: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.create_comment(id, text);
+    }
+
+    fn set_text(&mut self, id: u32, text: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.set_text(id, text);
+        myself.collect_garbage();
+    }
+
+    fn append_child(&mut self, parent: u32, child: u32) {
+        let myself = &mut *self.0.borrow_mut();
+        myself.dom.append_child(parent, child);
+        if parent == 0 {
+            myself.backend.append_to_root(&mut myself.dom, child);
+        }
+    }
+
+    fn insert_before(&mut self, anchor: u32, id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.insert_before(anchor, id);
+    }
+
+    fn clone_node(&mut self, id: u32, new_id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.clone_node(id, new_id);
+    }
+
+    fn copy(&mut self, from: u32, to: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.copy(from, to);
+    }
+
+    fn first_child(&mut self, id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.first_child(id);
+    }
+
+    fn next_sibling(&mut self, id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.next_sibling(id);
+    }
+
+    fn remove(&mut self, id: u32) {
+        let myself = &mut *self.0.borrow_mut();
+        myself.dom.remove(id);
+        let node = myself.dom.expect(id);
+        myself.backend.removed(&myself.dom, node);
+    }
+
+    fn return_node(&mut self, id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.dom.return_node(id);
+        myself.collect_garbage();
+    }
+
+    fn add_listener<E: EventDescription<DomRenderer<B>>>(
+        &mut self,
+        id: u32,
+        event: E,
+        callback: Box<dyn FnMut(B::Event)>,
+    ) {
+        let myself = &mut *self.0.borrow_mut();
+        myself
+            .backend
+            .add_listener(&mut myself.dom, id, event, callback);
+    }
+
+    fn get_template(&mut self, key: TypeId) -> Option<Vec<u32>> {
+        let myself = self.0.borrow();
+        myself.templates.get(&key).cloned()
+    }
+
+    fn set_template(&mut self, key: TypeId, roots: Vec<u32>) {
+        let mut myself = self.0.borrow_mut();
+        myself.templates.insert(key, roots);
+    }
+
+    fn set_doctype(&mut self, doctype: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.backend.set_doctype(doctype);
+    }
+
+    fn flush(&mut self) {
+        let mut myself = self.0.borrow_mut();
+        myself.backend.flush();
+    }
+
+    fn start_component(&mut self) {
+        let mut myself = self.0.borrow_mut();
+        myself.backend.start_component();
+    }
+
+    fn end_component(&mut self) {
+        let mut myself = self.0.borrow_mut();
+        myself.backend.end_component();
+    }
+
+    fn roots_finished(&mut self, roots: &[u32]) {
+        let myself = &mut *self.0.borrow_mut();
+        myself.backend.roots_finished(&mut myself.dom, roots);
+    }
+}
+
+fn escape_text(text: &str, out: &mut impl Write) -> fmt::Result {
+    for c in text.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            _ => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+fn escape_attribute(value: &str, out: &mut impl Write) -> fmt::Result {
+    for c in value.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '"' => out.write_str("&quot;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            _ => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Styles are set through the JS style object, so `backgroundColor` is written as `background-color`.
+fn write_css_name(name: &str, out: &mut impl Write) -> fmt::Result {
+    if name.starts_with("--") {
+        return out.write_str(name);
+    }
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            out.write_char('-')?;
+            out.write_char(c.to_ascii_lowercase())?;
+        } else {
+            out.write_char(c)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn escapes_html() {
+    let mut dom = Dom::default();
+    let div = dom.node();
+    dom.create_element(div, "div");
+    dom.set_attribute(div, "title", "\"quoted\" & <tagged>");
+    dom.set_style(div, "backgroundColor", "red");
+    let text = dom.node();
+    dom.create_text(text, "1 < 2 && 3 > 2");
+    dom.append_child(div, text);
+    let br = dom.node();
+    dom.create_element(br, "br");
+    dom.append_child(div, br);
+    dom.append_child(0, div);
+
+    let empty = dom.node();
+    dom.create_text(empty, "");
+    dom.append_child(div, empty);
+    let after = dom.node();
+    dom.create_text(after, "after");
+    dom.append_child(div, after);
+
+    assert_eq!(
+        dom.inner_html(dom.root()),
+        r#"<div title="&quot;quoted&quot; &amp; &lt;tagged&gt;" style="background-color:red;">1 &lt; 2 &amp;&amp; 3 &gt; 2<br><!--t--><!--t-->after</div>"#
+    );
+}
+
+#[test]
+fn frees_removed_nodes() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    thread_local! {
+        static FOCUSED: RefCell<Vec<i32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[component]
+    fn List(cx: Scope) {
+        let items: Rx<Vec<i32>> = vec![1, 2];
+
+        rsx! {
+            <ul>
+                <for item in {items.iter().copied()}>
+                    <li onfocus=|_| FOCUSED.with(|focused| focused.borrow_mut().push(item))>"{item}"</li>
+                </for>
+            </ul>
+            <button onclick=|_| { items.pop(); }>"pop"</button>
+            <p onclick=|_| {
+                let next = items.len() as i32 * 10;
+                items.push(next);
+            }>"push"</p>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), List {});
+    let nodes = ui.dom().nodes.len();
+    let removed = ui.dom().find_all("li")[1];
+    let button = ui.dom().find_all("button")[0];
+    let p = ui.dom().find_all("p")[0];
+
+    for _ in 0..3 {
+        ui.dispatch(button, crate::events::click);
+        ui.dispatch(p, crate::events::click);
+    }
+    // the removed rows are freed and their nodes are reused for the new ones
+    assert_eq!(ui.dom().nodes.len(), nodes);
+    let li = ui.dom().find_all("li")[1];
+    assert_eq!(li, removed);
+    assert_eq!(ui.dom().text_content(li), "10");
+
+    // only the listener of the new row runs
+    ui.dispatch(li, crate::events::focus);
+    assert_eq!(FOCUSED.with(|focused| focused.borrow().clone()), [10]);
+}
@@ -8,6 +8,13 @@ pub trait EventDescription<P: PlatformEvents> {
     const BUBBLES: bool = true;
 }
 
+/// Turns an event name that is only known at runtime back into its [`EventDescription`], see [`visit_event`].
+pub trait EventVisitor<P: PlatformEvents> {
+    type Output;
+
+    fn visit<E: EventDescription<P> + 'static>(self, event: E) -> Self::Output;
+}
+
 pub trait PlatformEvents {
     type AnimationEvent;
     type BeforeUnloadEvent;
@@ -77,8 +84,17 @@ macro_rules! generate_event_types {
     #[allow(dead_code, non_camel_case_types)]
     enum Events { $($event,)* __last}
 
+    #[cfg_attr(not(feature = "web"), allow(dead_code))]
     pub(crate) const EVENT_COUNT: usize = Events::__last as usize;
 
+    /// Call the visitor with the event named `name`, or return `None` if there is no such event.
+    pub fn visit_event<P: PlatformEvents, V: EventVisitor<P>>(name: &str, visitor: V) -> Option<V::Output> {
+        match name {
+            $(stringify!($event) => Some(visitor.visit($event)),)*
+            _ => None,
+        }
+    }
+
     $(
         #[doc = concat!("The [`", stringify!($event), "`](https://developer.mozilla.org/en-US/docs/Web/API/EventTarget/", stringify!($event), ") event, which receives ", stringify!($event_type), " as its argument.")]
         #[derive(Copy, Clone)]
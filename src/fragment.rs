@@ -1,43 +1,670 @@
-use crate::{
-    component::{ComponentState, DynComponentState},
-    prelude::{PlatformEvents, Renderer},
-};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
 
-pub struct Fragment<R: Renderer<P>, P: PlatformEvents> {
-    items: Vec<DynComponentState<R, P>>,
+use crate::component::ComponentState;
+use crate::content::Piece;
+use crate::prelude::{PlatformEvents, Renderer};
+
+/// The blocks a [`Region`] renders in front of its anchor, in order.
+#[derive(Debug, Default)]
+pub struct Fragment {
+    items: Vec<Block>,
+    // the `Vec<K>` of keys passed to the last `update_keyed`
+    keys: Option<Box<dyn Any>>,
+}
+
+impl Fragment {
+    pub fn new(items: Vec<Block>) -> Self {
+        Self { items, keys: None }
+    }
+
+    pub fn items_mut(&mut self) -> &mut [Block] {
+        &mut self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Replace every item. The new items are inserted in front of `anchor` if it is part of the tree.
+    pub fn update<R: Renderer<P>, P: PlatformEvents>(
+        &mut self,
+        iter: impl Iterator<Item = Block>,
+        anchor: Option<u32>,
+        ui: &mut R,
+    ) {
+        self.truncate(0, ui);
+        self.extend(iter, anchor, ui);
+        self.keys = None;
+    }
+
+    /// Update the items to match `items` by key.
+    ///
+    /// Items whose key was already there keep their block and are passed to `update`, other items are passed to `create`. Blocks of keys that are gone are removed. The longest run of kept blocks that are still in order stays in place and every other block is moved in front of the block after it.
+    pub fn update_keyed<R, P, K, T>(
+        &mut self,
+        items: Vec<(K, T)>,
+        anchor: Option<u32>,
+        ui: &mut R,
+        mut create: impl FnMut(&mut R, T) -> Block,
+        mut update: impl FnMut(&mut R, &mut Block, T),
+    ) where
+        R: Renderer<P>,
+        P: PlatformEvents,
+        K: Hash + Eq + 'static,
+    {
+        let old_keys = self
+            .keys
+            .take()
+            .and_then(|keys| keys.downcast::<Vec<K>>().ok())
+            .map(|keys| *keys)
+            .unwrap_or_default();
+        let old_items = std::mem::take(&mut self.items);
+
+        let mut old = HashMap::new();
+        if old_keys.len() == old_items.len() {
+            for (idx, (key, block)) in old_keys.into_iter().zip(old_items).enumerate() {
+                // only the last block of a duplicate key can be reused
+                if let Some((_, duplicate)) = old.insert(key, (idx, block)) {
+                    duplicate.remove(ui);
+                }
+            }
+        } else {
+            for block in old_items {
+                block.remove(ui);
+            }
+        }
+
+        let mut keys = Vec::with_capacity(items.len());
+        let mut sources = Vec::with_capacity(items.len());
+        for (key, item) in items {
+            match old.remove(&key) {
+                Some((idx, mut block)) => {
+                    update(ui, &mut block, item);
+                    self.items.push(block);
+                    sources.push(Some(idx));
+                }
+                None => {
+                    self.items.push(create(ui, item));
+                    sources.push(None);
+                }
+            }
+            keys.push(key);
+        }
+        for (_, block) in old.into_values() {
+            block.remove(ui);
+        }
+
+        if let Some(anchor) = anchor {
+            let stable = longest_increasing(&sources);
+            let mut next = anchor;
+            let mut roots = Vec::new();
+            for (idx, item) in self.items.iter().enumerate().rev() {
+                roots.clear();
+                item.roots(&mut roots);
+                match sources[idx] {
+                    None => {
+                        for root in &roots {
+                            ui.insert_before(next, *root);
+                        }
+                    }
+                    Some(_) if !stable[idx] => {
+                        for root in &roots {
+                            ui.move_before(next, *root);
+                        }
+                    }
+                    Some(_) => {}
+                }
+                if let Some(first) = roots.first() {
+                    next = *first;
+                }
+            }
+        }
+
+        self.keys = Some(Box::new(keys));
+    }
+
+    /// Remove the items after the first `len`.
+    pub fn truncate<R: Renderer<P>, P: PlatformEvents>(&mut self, len: usize, ui: &mut R) {
+        if len < self.items.len() {
+            for old in self.items.drain(len..) {
+                old.remove(ui);
+            }
+        }
+    }
+
+    /// Add items to the end. They are inserted in front of `anchor` if it is part of the tree.
+    pub fn extend<R: Renderer<P>, P: PlatformEvents>(
+        &mut self,
+        iter: impl Iterator<Item = Block>,
+        anchor: Option<u32>,
+        ui: &mut R,
+    ) {
+        for new in iter {
+            if let Some(anchor) = anchor {
+                let mut roots = Vec::new();
+                new.roots(&mut roots);
+                for root in roots {
+                    ui.insert_before(anchor, root);
+                }
+            }
+            self.items.push(new);
+        }
+    }
+
+    /// Update the items to match the pieces of some content by position. Text that stays text only updates its node, every other piece replaces the item in its place.
+    pub fn update_content<R, P>(
+        &mut self,
+        pieces: Vec<Piece<R, P>>,
+        anchor: Option<u32>,
+        ui: &mut R,
+    ) where
+        R: Renderer<P> + 'static,
+        P: PlatformEvents + 'static,
+    {
+        let len = pieces.len();
+        let mut pieces = pieces.into_iter();
+        for (idx, piece) in pieces.by_ref().take(self.items.len()).enumerate() {
+            match piece {
+                Piece::Text(text) if self.items[idx].is_text() => {
+                    ui.set_text(self.items[idx].node(0), &text);
+                }
+                piece => {
+                    let new = piece.create(ui);
+                    if anchor.is_some() {
+                        let mut old_roots = Vec::new();
+                        self.items[idx].roots(&mut old_roots);
+                        let mut roots = Vec::new();
+                        new.roots(&mut roots);
+                        for root in roots {
+                            ui.insert_before(old_roots[0], root);
+                        }
+                    }
+                    std::mem::replace(&mut self.items[idx], new).remove(ui);
+                }
+            }
+        }
+        let new: Vec<Block> = pieces.map(|piece| piece.create(ui)).collect();
+        self.extend(new.into_iter(), anchor, ui);
+        self.truncate(len, ui);
+        self.keys = None;
+    }
+
+    /// Push the top level nodes of every item in order.
+    pub fn roots(&self, roots: &mut Vec<u32>) {
+        for item in &self.items {
+            item.roots(roots);
+        }
+    }
+
+    fn release<R: Renderer<P>, P: PlatformEvents>(self, ui: &mut R) {
+        for item in self.items {
+            item.release(ui);
+        }
+    }
+}
+
+/// Mark the items in the longest run of old indexes that is still increasing. Those items are already in order and don't need to move.
+fn longest_increasing(sources: &[Option<usize>]) -> Vec<bool> {
+    // tails[len] is the item that ends the increasing run of length len + 1 with the smallest old index
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; sources.len()];
+    for (idx, source) in sources.iter().enumerate() {
+        let Some(source) = *source else {
+            continue;
+        };
+        let len = tails.partition_point(|tail| sources[*tail] < Some(source));
+        if len > 0 {
+            prev[idx] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(idx);
+        } else {
+            tails[len] = idx;
+        }
+    }
+
+    let mut stable = vec![false; sources.len()];
+    let mut current = tails.last().copied();
+    while let Some(idx) = current {
+        stable[idx] = true;
+        current = prev[idx];
+    }
+    stable
+}
+
+#[test]
+fn longest_increasing_run() {
+    let stable = longest_increasing(&[Some(3), Some(0), None, Some(1), Some(4), Some(2)]);
+    assert_eq!(stable, [false, true, false, true, false, true]);
+}
+
+/// A top level node of a [`Block`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockRoot {
+    Node(u32),
+    /// The index of a region in [`Block::region`], which renders its blocks and then its anchor.
+    Region(usize),
+    /// The index of a [`Child`] of the block, which renders the roots of the component and then its anchor.
+    Child(usize),
+}
+
+/// A component created inside a [`Block`]. The block keeps the state of the component alive and renders its roots in front of an anchor node.
+pub struct Child {
+    anchor: u32,
+    // reads the current roots, they can change after the component is created
+    roots: Box<dyn Fn() -> Vec<u32>>,
+}
+
+impl Child {
+    pub fn new<R, P, C>(anchor: u32, state: C) -> Self
+    where
+        R: Renderer<P>,
+        P: PlatformEvents,
+        C: ComponentState<R, P> + 'static,
+    {
+        Self {
+            anchor,
+            roots: Box::new(move || state.roots()),
+        }
+    }
+
+    /// Push the roots of the component followed by the anchor.
+    pub fn roots(&self, roots: &mut Vec<u32>) {
+        roots.extend((self.roots)());
+        roots.push(self.anchor);
+    }
+}
+
+impl std::fmt::Debug for Child {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Child")
+            .field("anchor", &self.anchor)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The nodes created for one branch of a [`Region`].
+#[derive(Debug, Default)]
+pub struct Block {
+    nodes: Vec<u32>,
+    // shared with the `Children` of the components in the block that render them
+    regions: Vec<Rc<RefCell<Region>>>,
+    children: Vec<Child>,
+    roots: Vec<BlockRoot>,
+    // the cell a loop block keeps its item in, so listeners read the current item when it is reused
+    item: Option<Box<dyn Any>>,
+}
+
+impl Block {
+    /// `nodes` are the ids the block owns, they are returned to the renderer when it is removed.
+    pub fn new(
+        nodes: Vec<u32>,
+        regions: Vec<Rc<RefCell<Region>>>,
+        children: Vec<Child>,
+        roots: Vec<BlockRoot>,
+    ) -> Self {
+        Self {
+            nodes,
+            regions,
+            children,
+            roots,
+            item: None,
+        }
+    }
+
+    pub fn node(&self, idx: usize) -> u32 {
+        self.nodes[idx]
+    }
+
+    pub fn set_item<T: 'static>(&mut self, item: T) {
+        self.item = Some(Box::new(item));
+    }
+
+    /// The value passed to the last [`Block::set_item`].
+    pub fn item<T: 'static>(&self) -> &T {
+        self.item
+            .as_ref()
+            .and_then(|item| item.downcast_ref())
+            .expect("the block has no item of this type")
+    }
+
+    pub fn region(&self, idx: usize) -> &Rc<RefCell<Region>> {
+        &self.regions[idx]
+    }
+
+    // the blocks of a content region are either a single text node or a component
+    fn is_text(&self) -> bool {
+        self.children.is_empty() && self.regions.is_empty()
+    }
+
+    /// Push the top level nodes of the block in order.
+    pub fn roots(&self, roots: &mut Vec<u32>) {
+        for root in &self.roots {
+            match root {
+                BlockRoot::Node(id) => roots.push(*id),
+                BlockRoot::Region(idx) => self.regions[*idx].borrow().roots(roots),
+                BlockRoot::Child(idx) => self.children[*idx].roots(roots),
+            }
+        }
+    }
+
+    /// Remove the block from the tree and return its ids to the renderer.
+    pub fn remove<R: Renderer<P>, P: PlatformEvents>(self, ui: &mut R) {
+        let mut roots = Vec::new();
+        self.roots(&mut roots);
+        for root in roots {
+            ui.remove(root);
+        }
+        self.release(ui);
+    }
+
+    fn release<R: Renderer<P>, P: PlatformEvents>(self, ui: &mut R) {
+        for region in self.regions {
+            let fragment = std::mem::take(&mut region.borrow_mut().fragment);
+            fragment.release(ui);
+        }
+        for id in self.nodes {
+            ui.return_node(id);
+        }
+    }
+}
+
+/// A dynamic part of the tree that renders a [`Fragment`] in front of an anchor node.
+#[derive(Debug)]
+pub struct Region {
+    anchor: u32,
+    mounted: bool,
+    branch: Option<usize>,
+    fragment: Fragment,
 }
 
-impl<R, P> Fragment<R, P>
-where
-    R: Renderer<P>,
-    P: PlatformEvents,
-{
-    pub fn new(items: Vec<DynComponentState<R, P>>) -> Self {
-        Self { items }
+impl Region {
+    /// A region whose anchor is a top level node is not `mounted` until its owner is inserted into the tree, its first blocks are inserted along with the owner's roots.
+    pub fn new(anchor: u32, mounted: bool) -> Self {
+        Self {
+            anchor,
+            mounted,
+            branch: None,
+            fragment: Fragment::default(),
+        }
+    }
+
+    pub fn anchor(&self) -> u32 {
+        self.anchor
+    }
+
+    /// The branch the current blocks were created for.
+    pub fn branch(&self) -> Option<usize> {
+        self.branch
+    }
+
+    pub fn blocks_mut(&mut self) -> &mut [Block] {
+        self.fragment.items_mut()
     }
 
-    pub fn update(
+    /// Push the nodes of every block followed by the anchor.
+    pub fn roots(&self, roots: &mut Vec<u32>) {
+        self.fragment.roots(roots);
+        roots.push(self.anchor);
+    }
+
+    fn mounted_anchor(&self) -> Option<u32> {
+        self.mounted.then_some(self.anchor)
+    }
+
+    /// Remove the current blocks and insert new ones for `branch`.
+    pub fn replace<R: Renderer<P>, P: PlatformEvents>(
         &mut self,
-        iter: impl Iterator<Item = DynComponentState<R, P>>,
-        parent: u32,
         ui: &mut R,
+        branch: Option<usize>,
+        blocks: Vec<Block>,
     ) {
-        for old in self.items.drain(..) {
-            old.remove(ui);
+        let anchor = self.mounted_anchor();
+        self.fragment.update(blocks.into_iter(), anchor, ui);
+        self.branch = branch;
+    }
+
+    /// Remove the blocks after the first `len`.
+    pub fn truncate<R: Renderer<P>, P: PlatformEvents>(&mut self, ui: &mut R, len: usize) {
+        self.fragment.truncate(len, ui);
+    }
+
+    /// Update the blocks to match `items` by key, see [`Fragment::update_keyed`].
+    pub fn update_keyed<R, P, K, T>(
+        &mut self,
+        ui: &mut R,
+        items: Vec<(K, T)>,
+        create: impl FnMut(&mut R, T) -> Block,
+        update: impl FnMut(&mut R, &mut Block, T),
+    ) where
+        R: Renderer<P>,
+        P: PlatformEvents,
+        K: Hash + Eq + 'static,
+    {
+        let anchor = self.mounted_anchor();
+        self.fragment
+            .update_keyed(items, anchor, ui, create, update);
+    }
+
+    /// Render the pieces of some content, see [`Fragment::update_content`].
+    pub fn update_content<R, P>(&mut self, ui: &mut R, pieces: Vec<Piece<R, P>>)
+    where
+        R: Renderer<P> + 'static,
+        P: PlatformEvents + 'static,
+    {
+        let anchor = self.mounted_anchor();
+        self.fragment.update_content(pieces, anchor, ui);
+    }
+
+    /// Add blocks after the current ones.
+    pub fn extend<R: Renderer<P>, P: PlatformEvents>(&mut self, ui: &mut R, blocks: Vec<Block>) {
+        let anchor = self.mounted_anchor();
+        self.fragment.extend(blocks.into_iter(), anchor, ui);
+    }
+
+    /// Mark the anchor as part of the tree so later blocks are inserted in front of it.
+    pub fn mount(&mut self) {
+        self.mounted = true;
+    }
+}
+
+#[test]
+fn conditions() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Toggle(cx: Scope) {
+        let count: Rx<i32> = 0;
+
+        rsx! {
+            <button onclick=|_| *count += 1>"next"</button>
+            <div>
+                <if {*count % 3 == 0}>
+                    <p>"fizz {count}"</p>
+                </if>
+                <elif {*count % 3 == 1}>
+                    "buzz"
+                </elif>
+                <else>
+                    <span onclick=|_| *count = 0>"reset"</span>
+                </else>
+            </div>
+            <if {*count > 0}>
+                <b>"{count}"</b>
+            </if>
         }
-        self.items = iter.collect();
-        for new in &self.items {
-            ui.append_all(parent, new.roots());
+    }
+
+    let ui = TestRenderer::launch(Toggle {});
+    let button = ui.find("button", 0);
+    let div = ui.find("div", 0);
+    assert_eq!(ui.dom().inner_html(div), "<p>fizz 0</p><!--t-->");
+    assert!(ui.dom().find_all("b").is_empty());
+
+    ui.click(button);
+    assert_eq!(ui.text(div), "buzz");
+    assert_eq!(ui.text(ui.find("b", 0)), "1");
+
+    ui.click(button);
+    assert_eq!(ui.text(div), "reset");
+    assert_eq!(ui.text(ui.find("b", 0)), "2");
+
+    // listeners inside a branch write to the component's state
+    ui.click(ui.find("span", 0));
+    assert_eq!(ui.dom().inner_html(div), "<p>fizz 0</p><!--t-->");
+    assert!(ui.dom().find_all("b").is_empty());
+
+    ui.click(button);
+    ui.click(button);
+    ui.click(button);
+    assert_eq!(ui.text(div), "fizz 3");
+    assert_eq!(ui.text(ui.dom().root()), "nextfizz 33");
+}
+
+#[test]
+fn loops() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn List(cx: Scope) {
+        let nums: Rx<Vec<i32>> = vec![1, 2];
+
+        rsx! {
+            <button onclick=|_| {
+                let next = nums.len() as i32 + 1;
+                nums.push(next);
+            }>"more"</button>
+            <ul>
+                <for num in {nums.iter().copied()}>
+                    <li onclick=|_| nums.retain(|n| *n != num)>"{num}"</li>
+                </for>
+                <li>"end"</li>
+            </ul>
         }
     }
+
+    let ui = TestRenderer::launch(List {});
+    let ul = ui.find("ul", 0);
+    assert_eq!(ui.text(ul), "12end");
+
+    ui.click(ui.find("button", 0));
+    assert_eq!(ui.text(ul), "123end");
+
+    // each item has its own listeners
+    ui.click(ui.find("li", 1));
+    assert_eq!(ui.text(ul), "13end");
+    assert_eq!(ui.dom().find_all("li").len(), 3);
+
+    // the block of the removed item now shows 3, its listener uses 3 as well
+    ui.click(ui.find("li", 1));
+    assert_eq!(ui.text(ul), "1end");
 }
 
-impl<R, P> ComponentState<R, P> for Fragment<R, P>
-where
-    R: Renderer<P>,
-    P: PlatformEvents,
-{
-    fn roots(&self) -> Vec<u32> {
-        self.items.iter().flat_map(|item| item.roots()).collect()
+#[test]
+fn keyed_loops() {
+    use crate::prelude::*;
+    use crate::recording::{to_text, RecordingRenderer};
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn List(cx: Scope) {
+        let nums: Rx<Vec<i32>> = vec![1, 2, 3, 4];
+
+        rsx! {
+            <button onclick=|_| nums.rotate_left(1)>"rotate"</button>
+            <button onclick=|_| {
+                nums.remove(1);
+                nums.push(5);
+            }>"replace"</button>
+            <ul>
+                <for num in {nums.iter().copied()} key={num}>
+                    <li>"{num}"</li>
+                </for>
+            </ul>
+        }
     }
+
+    let test = TestRenderer::default();
+    let ui = RecordingRenderer::new(test.clone());
+    launch(ui.clone(), List {});
+
+    let ul = test.find("ul", 0);
+    let first = test.find("li", 0);
+    assert_eq!(test.text(ul), "1234");
+    ui.take_ops();
+
+    // only the first item moves, the others keep their nodes
+    test.click(test.find("button", 0));
+    assert_eq!(test.text(ul), "2341");
+    assert_eq!(test.find("li", 3), first);
+    let ops = to_text(&ui.take_ops());
+    assert_eq!(ops.matches("move_before").count(), 1);
+    assert!(!ops.contains("create"));
+
+    test.click(test.find("button", 1));
+    assert_eq!(test.text(ul), "2415");
+    assert_eq!(test.dom().find_all("li").len(), 4);
+}
+
+#[test]
+fn matches() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    enum Status {
+        Loading,
+        Loaded(i32),
+        Failed(&'static str),
+    }
+
+    #[component]
+    fn App(cx: Scope) {
+        let status: Rx<Status> = Status::Loading;
+
+        rsx! {
+            <match {&*status}>
+                <case {Status::Loading}>"loading"</case>
+                <case {Status::Loaded(value)}>
+                    <b>"{value}"</b>
+                </case>
+                <case {Status::Failed(error)}>"error: {error}"</case>
+            </match>
+            <button onclick=|_| {
+                let next = match &*status {
+                    Status::Loading => Status::Loaded(1),
+                    Status::Loaded(1) => Status::Loaded(2),
+                    _ => Status::Failed("gone"),
+                };
+                *status = next;
+            }>"next"</button>
+        }
+    }
+
+    let ui = TestRenderer::launch(App {});
+    let root = ui.dom().root();
+    assert_eq!(ui.text(root), "loadingnext");
+
+    let button = ui.find("button", 0);
+    ui.click(button);
+    assert_eq!(ui.text(root), "1next");
+
+    // staying in the same arm keeps its nodes
+    let b = ui.find("b", 0);
+    ui.click(button);
+    assert_eq!(ui.text(root), "2next");
+    assert_eq!(ui.dom().find_all("b"), [b]);
+
+    ui.click(button);
+    assert_eq!(ui.text(root), "error: gonenext");
+    assert!(ui.dom().find_all("b").is_empty());
 }
@@ -1,17 +1,31 @@
 // pub mod copy;
 // pub mod copy_ll;
 pub mod component;
+pub mod content;
+pub mod dom;
 pub mod events;
 pub mod fragment;
 pub mod prelude;
+pub mod recording;
+pub mod remote;
 pub mod renderer;
 pub(crate) mod slab;
+#[cfg(feature = "ssr")]
+pub mod ssr;
+pub mod testing;
 mod tracking;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "web")]
 pub mod web;
 
+// lets the code generated by `qk_macro` refer to `qk::` from inside this crate
+extern crate self as qk;
+
 use component::{Component, ComponentState};
 use prelude::{PlatformEvents, Renderer};
 pub use qk_macro;
+use renderer::{Hydrate, HydrationError};
 
 pub fn launch<C, R: Renderer<R> + PlatformEvents + Sized>(mut ui: R, props: C)
 where
@@ -21,3 +35,41 @@ where
     ui.append_all(0, comp.roots());
     ui.flush();
 }
+
+/// Take over HTML that was rendered on the server by [`render_to_string`] inside the root.
+///
+/// The existing nodes are reused in place of the template clones and only the listeners are attached. If the HTML does not match the component, for example because it is stale, an error is returned and the page is left as the server rendered it, it can be cleared and the component launched instead.
+pub fn hydrate<C, R>(mut ui: R, props: C) -> Result<(), HydrationError>
+where
+    C: Component<R, R>,
+    R: Renderer<R> + PlatformEvents + Hydrate,
+{
+    ui.start_hydration();
+    let comp = props.create(&mut ui);
+    let hydrated = ui.finish_hydration(&comp.roots());
+    ui.flush();
+    hydrated
+}
+
+/// Render a component to an HTML string.
+#[cfg(feature = "ssr")]
+pub fn render_to_string<C>(props: C) -> String
+where
+    C: Component<ssr::SsrRenderer, ssr::SsrRenderer>,
+{
+    let ui = ssr::SsrRenderer::default();
+    launch(ui.clone(), props);
+    ui.html()
+}
+
+/// Render a component to a writer, writing and flushing each of its roots as soon as the child components inside it are created.
+#[cfg(feature = "ssr")]
+pub fn render_to_writer<C, W>(props: C, writer: W) -> std::io::Result<W>
+where
+    C: Component<ssr::StreamingRenderer<W>, ssr::StreamingRenderer<W>>,
+    W: std::io::Write + 'static,
+{
+    let ui = ssr::StreamingRenderer::new(writer);
+    launch(ui.clone(), props);
+    ui.finish()
+}
@@ -1,6 +1,17 @@
+pub use crate::component::Children;
+pub use crate::content::iter;
 pub use crate::events::PlatformEvents;
+pub use crate::hydrate;
 pub use crate::launch;
 pub use crate::renderer::Renderer;
-pub use crate::tracking::{DirtyTrack, DirtyTrackSet, Effect, RwTrack};
+#[cfg(feature = "ssr")]
+pub use crate::ssr::SsrRenderer;
+pub use crate::testing::TestRenderer;
+pub use crate::tracking::{BitSet, Bits, DirtyTrack, DirtyTrackSet, Effect, RwTrack};
+#[cfg(feature = "tui")]
+pub use crate::tui::TuiRenderer;
+#[cfg(feature = "web")]
 pub use crate::web::WebRenderer;
+#[cfg(feature = "ssr")]
+pub use crate::{render_to_string, render_to_writer};
 pub use qk_macro::*;
@@ -0,0 +1,706 @@
+use std::any::TypeId;
+use std::cell::{Ref, RefCell};
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    events::{EventDescription, PlatformEvents},
+    renderer::Renderer,
+};
+
+/// A single operation a component asked a renderer to perform.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    Node {
+        id: u32,
+    },
+    SetAttribute {
+        id: u32,
+        name: String,
+        value: String,
+    },
+    SetStyle {
+        id: u32,
+        name: String,
+        value: String,
+    },
+    CreateElement {
+        id: u32,
+        tag: String,
+    },
+    CreateText {
+        id: u32,
+        text: String,
+    },
+    SetText {
+        id: u32,
+        text: String,
+    },
+    AppendChild {
+        parent: u32,
+        child: u32,
+    },
+    CloneNode {
+        id: u32,
+        new_id: u32,
+    },
+    Copy {
+        from: u32,
+        to: u32,
+    },
+    FirstChild {
+        id: u32,
+    },
+    NextSibling {
+        id: u32,
+    },
+    Remove {
+        id: u32,
+    },
+    ReturnNode {
+        id: u32,
+    },
+    AddListener {
+        id: u32,
+        event: String,
+    },
+    Flush,
+    CreateElementNs {
+        id: u32,
+        tag: String,
+        namespace: String,
+    },
+    SetAttributeNs {
+        id: u32,
+        name: String,
+        namespace: String,
+        value: String,
+    },
+    RemoveAttribute {
+        id: u32,
+        name: String,
+    },
+    InsertBefore {
+        anchor: u32,
+        id: u32,
+    },
+    MoveBefore {
+        anchor: u32,
+        id: u32,
+    },
+    CreateComment {
+        id: u32,
+        text: String,
+    },
+}
+
+/// The error returned when bytes do not contain a valid [`Op`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    UnknownOp(u8),
+    InvalidUtf8,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::UnknownOp(op) => write!(f, "unknown op code {op}"),
+            DecodeError::InvalidUtf8 => write!(f, "string is not valid utf-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Op {
+    /// Append the binary form of the op: a one byte op code followed by little endian `u32`s and length prefixed strings.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        fn id(out: &mut Vec<u8>, id: u32) {
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        fn string(out: &mut Vec<u8>, string: &str) {
+            id(out, string.len() as u32);
+            out.extend_from_slice(string.as_bytes());
+        }
+
+        match self {
+            Op::Node { id: node } => {
+                out.push(0);
+                id(out, *node);
+            }
+            Op::SetAttribute {
+                id: node,
+                name,
+                value,
+            } => {
+                out.push(1);
+                id(out, *node);
+                string(out, name);
+                string(out, value);
+            }
+            Op::SetStyle {
+                id: node,
+                name,
+                value,
+            } => {
+                out.push(2);
+                id(out, *node);
+                string(out, name);
+                string(out, value);
+            }
+            Op::CreateElement { id: node, tag } => {
+                out.push(3);
+                id(out, *node);
+                string(out, tag);
+            }
+            Op::CreateText { id: node, text } => {
+                out.push(4);
+                id(out, *node);
+                string(out, text);
+            }
+            Op::SetText { id: node, text } => {
+                out.push(5);
+                id(out, *node);
+                string(out, text);
+            }
+            Op::AppendChild { parent, child } => {
+                out.push(6);
+                id(out, *parent);
+                id(out, *child);
+            }
+            Op::CloneNode { id: node, new_id } => {
+                out.push(7);
+                id(out, *node);
+                id(out, *new_id);
+            }
+            Op::Copy { from, to } => {
+                out.push(8);
+                id(out, *from);
+                id(out, *to);
+            }
+            Op::FirstChild { id: node } => {
+                out.push(9);
+                id(out, *node);
+            }
+            Op::NextSibling { id: node } => {
+                out.push(10);
+                id(out, *node);
+            }
+            Op::Remove { id: node } => {
+                out.push(11);
+                id(out, *node);
+            }
+            Op::ReturnNode { id: node } => {
+                out.push(12);
+                id(out, *node);
+            }
+            Op::AddListener { id: node, event } => {
+                out.push(13);
+                id(out, *node);
+                string(out, event);
+            }
+            Op::Flush => out.push(14),
+            Op::CreateElementNs {
+                id: node,
+                tag,
+                namespace,
+            } => {
+                out.push(15);
+                id(out, *node);
+                string(out, tag);
+                string(out, namespace);
+            }
+            Op::SetAttributeNs {
+                id: node,
+                name,
+                namespace,
+                value,
+            } => {
+                out.push(16);
+                id(out, *node);
+                string(out, name);
+                string(out, namespace);
+                string(out, value);
+            }
+            Op::RemoveAttribute { id: node, name } => {
+                out.push(17);
+                id(out, *node);
+                string(out, name);
+            }
+            Op::InsertBefore { anchor, id: node } => {
+                out.push(18);
+                id(out, *anchor);
+                id(out, *node);
+            }
+            Op::MoveBefore { anchor, id: node } => {
+                out.push(19);
+                id(out, *anchor);
+                id(out, *node);
+            }
+            Op::CreateComment { id: node, text } => {
+                out.push(20);
+                id(out, *node);
+                string(out, text);
+            }
+        }
+    }
+
+    /// Read one op from the front of `bytes`, advancing past it.
+    pub fn decode(bytes: &mut &[u8]) -> Result<Self, DecodeError> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+            if bytes.len() < len {
+                return Err(DecodeError::UnexpectedEnd);
+            }
+            let (taken, rest) = bytes.split_at(len);
+            *bytes = rest;
+            Ok(taken)
+        }
+        fn id(bytes: &mut &[u8]) -> Result<u32, DecodeError> {
+            let taken = take(bytes, 4)?;
+            Ok(u32::from_le_bytes(taken.try_into().unwrap()))
+        }
+        fn string(bytes: &mut &[u8]) -> Result<String, DecodeError> {
+            let len = id(bytes)? as usize;
+            let taken = take(bytes, len)?;
+            String::from_utf8(taken.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+        }
+
+        let op = take(bytes, 1)?[0];
+        Ok(match op {
+            0 => Op::Node { id: id(bytes)? },
+            1 => Op::SetAttribute {
+                id: id(bytes)?,
+                name: string(bytes)?,
+                value: string(bytes)?,
+            },
+            2 => Op::SetStyle {
+                id: id(bytes)?,
+                name: string(bytes)?,
+                value: string(bytes)?,
+            },
+            3 => Op::CreateElement {
+                id: id(bytes)?,
+                tag: string(bytes)?,
+            },
+            4 => Op::CreateText {
+                id: id(bytes)?,
+                text: string(bytes)?,
+            },
+            5 => Op::SetText {
+                id: id(bytes)?,
+                text: string(bytes)?,
+            },
+            6 => Op::AppendChild {
+                parent: id(bytes)?,
+                child: id(bytes)?,
+            },
+            7 => Op::CloneNode {
+                id: id(bytes)?,
+                new_id: id(bytes)?,
+            },
+            8 => Op::Copy {
+                from: id(bytes)?,
+                to: id(bytes)?,
+            },
+            9 => Op::FirstChild { id: id(bytes)? },
+            10 => Op::NextSibling { id: id(bytes)? },
+            11 => Op::Remove { id: id(bytes)? },
+            12 => Op::ReturnNode { id: id(bytes)? },
+            13 => Op::AddListener {
+                id: id(bytes)?,
+                event: string(bytes)?,
+            },
+            14 => Op::Flush,
+            15 => Op::CreateElementNs {
+                id: id(bytes)?,
+                tag: string(bytes)?,
+                namespace: string(bytes)?,
+            },
+            16 => Op::SetAttributeNs {
+                id: id(bytes)?,
+                name: string(bytes)?,
+                namespace: string(bytes)?,
+                value: string(bytes)?,
+            },
+            17 => Op::RemoveAttribute {
+                id: id(bytes)?,
+                name: string(bytes)?,
+            },
+            18 => Op::InsertBefore {
+                anchor: id(bytes)?,
+                id: id(bytes)?,
+            },
+            19 => Op::MoveBefore {
+                anchor: id(bytes)?,
+                id: id(bytes)?,
+            },
+            20 => Op::CreateComment {
+                id: id(bytes)?,
+                text: string(bytes)?,
+            },
+            op => return Err(DecodeError::UnknownOp(op)),
+        })
+    }
+}
+
+/// The text form of an op: the op name followed by its arguments, with strings quoted and escaped like Rust strings.
+impl Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Node { id } => write!(f, "node {id}"),
+            Op::SetAttribute { id, name, value } => {
+                write!(f, "set_attribute {id} {name:?} {value:?}")
+            }
+            Op::SetStyle { id, name, value } => write!(f, "set_style {id} {name:?} {value:?}"),
+            Op::CreateElement { id, tag } => write!(f, "create_element {id} {tag:?}"),
+            Op::CreateText { id, text } => write!(f, "create_text {id} {text:?}"),
+            Op::SetText { id, text } => write!(f, "set_text {id} {text:?}"),
+            Op::AppendChild { parent, child } => write!(f, "append_child {parent} {child}"),
+            Op::CloneNode { id, new_id } => write!(f, "clone_node {id} {new_id}"),
+            Op::Copy { from, to } => write!(f, "copy {from} {to}"),
+            Op::FirstChild { id } => write!(f, "first_child {id}"),
+            Op::NextSibling { id } => write!(f, "next_sibling {id}"),
+            Op::Remove { id } => write!(f, "remove {id}"),
+            Op::ReturnNode { id } => write!(f, "return_node {id}"),
+            Op::AddListener { id, event } => write!(f, "add_listener {id} {event}"),
+            Op::Flush => write!(f, "flush"),
+            Op::CreateElementNs { id, tag, namespace } => {
+                write!(f, "create_element_ns {id} {tag:?} {namespace:?}")
+            }
+            Op::SetAttributeNs {
+                id,
+                name,
+                namespace,
+                value,
+            } => write!(f, "set_attribute_ns {id} {name:?} {namespace:?} {value:?}"),
+            Op::RemoveAttribute { id, name } => write!(f, "remove_attribute {id} {name:?}"),
+            Op::InsertBefore { anchor, id } => write!(f, "insert_before {anchor} {id}"),
+            Op::MoveBefore { anchor, id } => write!(f, "move_before {anchor} {id}"),
+            Op::CreateComment { id, text } => write!(f, "create_comment {id} {text:?}"),
+        }
+    }
+}
+
+/// Serialize ops as text, one op per line.
+pub fn to_text(ops: &[Op]) -> String {
+    let mut text = String::new();
+    for op in ops {
+        text += &op.to_string();
+        text.push('\n');
+    }
+    text
+}
+
+/// Serialize ops in the binary format described in [`Op::encode`].
+pub fn encode(ops: &[Op]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for op in ops {
+        op.encode(&mut bytes);
+    }
+    bytes
+}
+
+/// Read every op from bytes created by [`encode`].
+pub fn decode(mut bytes: &[u8]) -> Result<Vec<Op>, DecodeError> {
+    let mut ops = Vec::new();
+    while !bytes.is_empty() {
+        ops.push(Op::decode(&mut bytes)?);
+    }
+    Ok(ops)
+}
+
+/// A renderer that logs every operation before passing it on to another renderer.
+#[derive(Clone)]
+pub struct RecordingRenderer<R> {
+    inner: R,
+    ops: Rc<RefCell<Vec<Op>>>,
+}
+
+impl<R> RecordingRenderer<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            ops: Default::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Every op recorded since the last call to [`RecordingRenderer::take_ops`].
+    pub fn ops(&self) -> Ref<'_, [Op]> {
+        Ref::map(self.ops.borrow(), |ops| ops.as_slice())
+    }
+
+    /// Remove and return the recorded ops so the next batch can be inspected on its own.
+    pub fn take_ops(&self) -> Vec<Op> {
+        std::mem::take(&mut *self.ops.borrow_mut())
+    }
+
+    fn record(&self, op: Op) {
+        self.ops.borrow_mut().push(op);
+    }
+}
+
+impl<R: PlatformEvents> PlatformEvents for RecordingRenderer<R> {
+    type AnimationEvent = R::AnimationEvent;
+    type BeforeUnloadEvent = R::BeforeUnloadEvent;
+    type CompositionEvent = R::CompositionEvent;
+    type DeviceMotionEvent = R::DeviceMotionEvent;
+    type DeviceOrientationEvent = R::DeviceOrientationEvent;
+    type DragEvent = R::DragEvent;
+    type ErrorEvent = R::ErrorEvent;
+    type Event = R::Event;
+    type FocusEvent = R::FocusEvent;
+    type GamepadEvent = R::GamepadEvent;
+    type HashChangeEvent = R::HashChangeEvent;
+    type InputEvent = R::InputEvent;
+    type KeyboardEvent = R::KeyboardEvent;
+    type MessageEvent = R::MessageEvent;
+    type MouseEvent = R::MouseEvent;
+    type PageTransitionEvent = R::PageTransitionEvent;
+    type PointerEvent = R::PointerEvent;
+    type PopStateEvent = R::PopStateEvent;
+    type PromiseRejectionEvent = R::PromiseRejectionEvent;
+    type SecurityPolicyViolationEvent = R::SecurityPolicyViolationEvent;
+    type StorageEvent = R::StorageEvent;
+    type SubmitEvent = R::SubmitEvent;
+    type TouchEvent = R::TouchEvent;
+    type TransitionEvent = R::TransitionEvent;
+    type UiEvent = R::UiEvent;
+    type WheelEvent = R::WheelEvent;
+    type ProgressEvent = R::ProgressEvent;
+}
+
+/// Describes an event of the wrapping renderer as an event of the inner renderer.
+struct Forward<E>(PhantomData<E>);
+
+impl<R: PlatformEvents, E: EventDescription<RecordingRenderer<R>>> EventDescription<R>
+    for Forward<E>
+{
+    type EventType = E::EventType;
+
+    const ID: u16 = E::ID;
+
+    const NAME: &'static str = E::NAME;
+
+    const BUBBLES: bool = E::BUBBLES;
+}
+
+impl<R: Renderer<R> + PlatformEvents> Renderer<RecordingRenderer<R>> for RecordingRenderer<R> {
+    fn node(&mut self) -> u32 {
+        let id = self.inner.node();
+        self.record(Op::Node { id });
+        id
+    }
+
+    fn append_all(&mut self, parent: u32, children: impl IntoIterator<Item = u32>) {
+        for child in children.into_iter() {
+            self.append_child(parent, child);
+        }
+    }
+
+    fn set_attribute(&mut self, id: u32, name: &'static str, value: &str) {
+        self.record(Op::SetAttribute {
+            id,
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        self.inner.set_attribute(id, name, value)
+    }
+
+    fn set_attribute_ns(
+        &mut self,
+        id: u32,
+        name: &'static str,
+        namespace: &'static str,
+        value: &str,
+    ) {
+        self.record(Op::SetAttributeNs {
+            id,
+            name: name.to_string(),
+            namespace: namespace.to_string(),
+            value: value.to_string(),
+        });
+        self.inner.set_attribute_ns(id, name, namespace, value)
+    }
+
+    fn remove_attribute(&mut self, id: u32, name: &'static str) {
+        self.record(Op::RemoveAttribute {
+            id,
+            name: name.to_string(),
+        });
+        self.inner.remove_attribute(id, name)
+    }
+
+    fn set_style(&mut self, id: u32, name: &'static str, value: &str) {
+        self.record(Op::SetStyle {
+            id,
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        self.inner.set_style(id, name, value)
+    }
+
+    fn create_element(&mut self, id: u32, tag: &'static str) {
+        self.record(Op::CreateElement {
+            id,
+            tag: tag.to_string(),
+        });
+        self.inner.create_element(id, tag)
+    }
+
+    fn create_element_ns(&mut self, id: u32, tag: &'static str, namespace: &'static str) {
+        self.record(Op::CreateElementNs {
+            id,
+            tag: tag.to_string(),
+            namespace: namespace.to_string(),
+        });
+        self.inner.create_element_ns(id, tag, namespace)
+    }
+
+    fn create_text(&mut self, id: u32, text: &str) {
+        self.record(Op::CreateText {
+            id,
+            text: text.to_string(),
+        });
+        self.inner.create_text(id, text)
+    }
+
+    fn create_comment(&mut self, id: u32, text: &str) {
+        self.record(Op::CreateComment {
+            id,
+            text: text.to_string(),
+        });
+        self.inner.create_comment(id, text)
+    }
+
+    fn set_text(&mut self, id: u32, text: &str) {
+        self.record(Op::SetText {
+            id,
+            text: text.to_string(),
+        });
+        self.inner.set_text(id, text)
+    }
+
+    fn append_child(&mut self, parent: u32, child: u32) {
+        self.record(Op::AppendChild { parent, child });
+        self.inner.append_child(parent, child)
+    }
+
+    fn insert_before(&mut self, anchor: u32, id: u32) {
+        self.record(Op::InsertBefore { anchor, id });
+        self.inner.insert_before(anchor, id)
+    }
+
+    fn move_before(&mut self, anchor: u32, id: u32) {
+        self.record(Op::MoveBefore { anchor, id });
+        self.inner.move_before(anchor, id)
+    }
+
+    fn clone_node(&mut self, id: u32, new_id: u32) {
+        self.record(Op::CloneNode { id, new_id });
+        self.inner.clone_node(id, new_id)
+    }
+
+    fn copy(&mut self, from: u32, to: u32) {
+        self.record(Op::Copy { from, to });
+        self.inner.copy(from, to)
+    }
+
+    fn first_child(&mut self, id: u32) {
+        self.record(Op::FirstChild { id });
+        self.inner.first_child(id)
+    }
+
+    fn next_sibling(&mut self, id: u32) {
+        self.record(Op::NextSibling { id });
+        self.inner.next_sibling(id)
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.record(Op::Remove { id });
+        self.inner.remove(id)
+    }
+
+    fn return_node(&mut self, id: u32) {
+        self.record(Op::ReturnNode { id });
+        self.inner.return_node(id)
+    }
+
+    fn add_listener<E: EventDescription<RecordingRenderer<R>>>(
+        &mut self,
+        id: u32,
+        _: E,
+        callback: Box<dyn FnMut(R::Event)>,
+    ) {
+        self.record(Op::AddListener {
+            id,
+            event: E::NAME.to_string(),
+        });
+        self.inner
+            .add_listener(id, Forward::<E>(PhantomData), callback)
+    }
+
+    fn get_template(&mut self, key: TypeId) -> Option<Vec<u32>> {
+        self.inner.get_template(key)
+    }
+
+    fn set_template(&mut self, key: TypeId, roots: Vec<u32>) {
+        self.inner.set_template(key, roots)
+    }
+
+    // the doctype is not recorded, it doesn't change the tree
+    fn set_doctype(&mut self, doctype: &str) {
+        self.inner.set_doctype(doctype)
+    }
+
+    fn flush(&mut self) {
+        self.record(Op::Flush);
+        self.inner.flush()
+    }
+
+    fn start_component(&mut self) {
+        self.inner.start_component()
+    }
+
+    fn end_component(&mut self) {
+        self.inner.end_component()
+    }
+
+    fn roots_finished(&mut self, roots: &[u32]) {
+        self.inner.roots_finished(roots)
+    }
+}
+
+#[test]
+fn records_updates() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Counter(cx: Scope) {
+        let num: Rx<i32> = 0;
+
+        rsx! {
+            <button onclick=|_| *num += 1>
+                "count: {num}"
+            </button>
+        }
+    }
+
+    let test = TestRenderer::default();
+    let ui = RecordingRenderer::new(test.clone());
+    launch(ui.clone(), Counter {});
+
+    let created = ui.take_ops();
+    assert_eq!(decode(&encode(&created)).unwrap(), created);
+
+    let button = test.dom().find_all("button")[0];
+    test.dispatch(button, crate::events::click);
+    assert_eq!(to_text(&ui.take_ops()), "set_text 2 \"count: 1\"\nflush\n");
+}
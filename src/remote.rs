@@ -0,0 +1,716 @@
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use crate::recording::{DecodeError, Op};
+use crate::slab::IdSlab;
+use crate::{
+    events::{visit_event, EventDescription, EventVisitor, PlatformEvents},
+    renderer::Renderer,
+};
+
+/// A renderer that sends every operation to a [`RemoteClient`] in another process.
+///
+/// Ops are encoded with [`Op::encode`] and written to `W` when the component flushes. Events come back through [`RemoteRenderer::handle_events`].
+pub struct RemoteRenderer<W: Write>(Rc<RefCell<RemoteRendererInner<W>>>);
+
+pub struct RemoteRendererInner<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    ids: IdSlab<()>,
+    templates: HashMap<TypeId, Vec<u32>>,
+    // the event names and handler ids of the listeners of each node, removed when the id is returned
+    listeners: HashMap<u32, Vec<(&'static str, u32)>>,
+    event_handlers: SharedHandlers,
+    error: Option<io::Error>,
+}
+
+/// An event sent back from a [`RemoteClient`], and the event type every [`RemoteRenderer`] event resolves to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteEvent {
+    /// The id of the node the listener was added to on the [`RemoteRenderer`].
+    pub id: u32,
+    pub name: String,
+}
+
+impl RemoteEvent {
+    /// Append the binary form of the event: a little endian `u32` id followed by the length prefixed name.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.id.to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+    }
+
+    /// Read one event from the front of `bytes`, advancing past it.
+    pub fn decode(bytes: &mut &[u8]) -> Result<Self, DecodeError> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+            if bytes.len() < len {
+                return Err(DecodeError::UnexpectedEnd);
+            }
+            let (taken, rest) = bytes.split_at(len);
+            *bytes = rest;
+            Ok(taken)
+        }
+        fn id(bytes: &mut &[u8]) -> Result<u32, DecodeError> {
+            let taken = take(bytes, 4)?;
+            Ok(u32::from_le_bytes(taken.try_into().unwrap()))
+        }
+
+        let node = id(bytes)?;
+        let len = id(bytes)? as usize;
+        let name = take(bytes, len)?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| DecodeError::InvalidUtf8)?;
+        Ok(RemoteEvent { id: node, name })
+    }
+}
+
+impl<W: Write> Clone for RemoteRenderer<W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<W: Write> RemoteRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        let mut ids = IdSlab::default();
+
+        // the root node
+        ids.id(());
+
+        Self(Rc::new(RefCell::new(RemoteRendererInner {
+            writer,
+            buffer: Vec::new(),
+            ids,
+            templates: HashMap::new(),
+            listeners: HashMap::new(),
+            event_handlers: SharedHandlers::default(),
+            error: None,
+        })))
+    }
+
+    /// The first error writing ops, if any. Nothing is written after an error.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.0.borrow_mut().error.take()
+    }
+
+    /// Run the listeners for events encoded by a [`RemoteClient`].
+    ///
+    /// The client already bubbled the events, so each one only runs the listener it was sent for.
+    pub fn handle_events(&self, mut bytes: &[u8]) -> Result<(), DecodeError> {
+        while !bytes.is_empty() {
+            let event = RemoteEvent::decode(&mut bytes)?;
+            // clone the handlers out so they can use the renderer, and remove their own node, while they run
+            let handlers: Vec<Handler> = {
+                let myself = self.0.borrow();
+                let handlers = myself.event_handlers.event_handlers.borrow();
+                (myself.listeners.get(&event.id).into_iter().flatten())
+                    .filter(|(name, _)| *name == event.name)
+                    .map(|(_, handler_id)| {
+                        handlers
+                            .get(*handler_id)
+                            .expect("handler not found")
+                            .clone()
+                    })
+                    .collect()
+            };
+
+            for handler in handlers {
+                (handler.borrow_mut())(event.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, op: Op) {
+        let mut myself = self.0.borrow_mut();
+        op.encode(&mut myself.buffer);
+    }
+}
+
+impl<W: Write> PlatformEvents for RemoteRenderer<W> {
+    type AnimationEvent = RemoteEvent;
+    type BeforeUnloadEvent = RemoteEvent;
+    type CompositionEvent = RemoteEvent;
+    type DeviceMotionEvent = RemoteEvent;
+    type DeviceOrientationEvent = RemoteEvent;
+    type DragEvent = RemoteEvent;
+    type ErrorEvent = RemoteEvent;
+    type FocusEvent = RemoteEvent;
+    type GamepadEvent = RemoteEvent;
+    type HashChangeEvent = RemoteEvent;
+    type InputEvent = RemoteEvent;
+    type KeyboardEvent = RemoteEvent;
+    type MessageEvent = RemoteEvent;
+    type MouseEvent = RemoteEvent;
+    type PageTransitionEvent = RemoteEvent;
+    type PointerEvent = RemoteEvent;
+    type PopStateEvent = RemoteEvent;
+    type PromiseRejectionEvent = RemoteEvent;
+    type SecurityPolicyViolationEvent = RemoteEvent;
+    type StorageEvent = RemoteEvent;
+    type SubmitEvent = RemoteEvent;
+    type TouchEvent = RemoteEvent;
+    type TransitionEvent = RemoteEvent;
+    type UiEvent = RemoteEvent;
+    type WheelEvent = RemoteEvent;
+    type ProgressEvent = RemoteEvent;
+    type Event = RemoteEvent;
+}
+
+impl<W: Write> Renderer<RemoteRenderer<W>> for RemoteRenderer<W> {
+    fn node(&mut self) -> u32 {
+        let id = self.0.borrow_mut().ids.id(());
+        self.send(Op::Node { id });
+        id
+    }
+
+    fn append_all(&mut self, parent: u32, children: impl IntoIterator<Item = u32>) {
+        for child in children.into_iter() {
+            self.append_child(parent, child);
+        }
+    }
+
+    fn set_attribute(&mut self, id: u32, name: &'static str, value: &str) {
+        self.send(Op::SetAttribute {
+            id,
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    fn set_attribute_ns(
+        &mut self,
+        id: u32,
+        name: &'static str,
+        namespace: &'static str,
+        value: &str,
+    ) {
+        self.send(Op::SetAttributeNs {
+            id,
+            name: name.to_string(),
+            namespace: namespace.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    fn remove_attribute(&mut self, id: u32, name: &'static str) {
+        self.send(Op::RemoveAttribute {
+            id,
+            name: name.to_string(),
+        });
+    }
+
+    fn set_style(&mut self, id: u32, name: &'static str, value: &str) {
+        self.send(Op::SetStyle {
+            id,
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    fn create_element(&mut self, id: u32, tag: &'static str) {
+        self.send(Op::CreateElement {
+            id,
+            tag: tag.to_string(),
+        });
+    }
+
+    fn create_element_ns(&mut self, id: u32, tag: &'static str, namespace: &'static str) {
+        self.send(Op::CreateElementNs {
+            id,
+            tag: tag.to_string(),
+            namespace: namespace.to_string(),
+        });
+    }
+
+    fn create_text(&mut self, id: u32, text: &str) {
+        self.send(Op::CreateText {
+            id,
+            text: text.to_string(),
+        });
+    }
+
+    fn create_comment(&mut self, id: u32, text: &str) {
+        self.send(Op::CreateComment {
+            id,
+            text: text.to_string(),
+        });
+    }
+
+    fn set_text(&mut self, id: u32, text: &str) {
+        self.send(Op::SetText {
+            id,
+            text: text.to_string(),
+        });
+    }
+
+    fn append_child(&mut self, parent: u32, child: u32) {
+        self.send(Op::AppendChild { parent, child });
+    }
+
+    fn insert_before(&mut self, anchor: u32, id: u32) {
+        self.send(Op::InsertBefore { anchor, id });
+    }
+
+    fn move_before(&mut self, anchor: u32, id: u32) {
+        self.send(Op::MoveBefore { anchor, id });
+    }
+
+    fn clone_node(&mut self, id: u32, new_id: u32) {
+        self.send(Op::CloneNode { id, new_id });
+    }
+
+    fn copy(&mut self, from: u32, to: u32) {
+        self.send(Op::Copy { from, to });
+    }
+
+    fn first_child(&mut self, id: u32) {
+        self.send(Op::FirstChild { id });
+    }
+
+    fn next_sibling(&mut self, id: u32) {
+        self.send(Op::NextSibling { id });
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.send(Op::Remove { id });
+    }
+
+    fn return_node(&mut self, id: u32) {
+        {
+            let mut myself = self.0.borrow_mut();
+            myself.ids.recycle(id);
+            // the next node with this id must not run the listeners of this one
+            if let Some(listeners) = myself.listeners.remove(&id) {
+                let mut handlers = myself.event_handlers.event_handlers.borrow_mut();
+                for (_, handler_id) in listeners {
+                    handlers.recycle(handler_id);
+                }
+            }
+        }
+        self.send(Op::ReturnNode { id });
+    }
+
+    fn add_listener<E: EventDescription<RemoteRenderer<W>>>(
+        &mut self,
+        id: u32,
+        _: E,
+        callback: Box<dyn FnMut(RemoteEvent)>,
+    ) {
+        {
+            let mut myself = self.0.borrow_mut();
+            let handler_id = myself
+                .event_handlers
+                .event_handlers
+                .borrow_mut()
+                .id(Rc::new(RefCell::new(callback)));
+            myself
+                .listeners
+                .entry(id)
+                .or_default()
+                .push((E::NAME, handler_id));
+        }
+        self.send(Op::AddListener {
+            id,
+            event: E::NAME.to_string(),
+        });
+    }
+
+    fn get_template(&mut self, key: TypeId) -> Option<Vec<u32>> {
+        let myself = self.0.borrow();
+        myself.templates.get(&key).cloned()
+    }
+
+    fn set_template(&mut self, key: TypeId, roots: Vec<u32>) {
+        let mut myself = self.0.borrow_mut();
+        myself.templates.insert(key, roots);
+    }
+
+    fn flush(&mut self) {
+        self.send(Op::Flush);
+        let myself = &mut *self.0.borrow_mut();
+        let buffer = std::mem::take(&mut myself.buffer);
+        if myself.error.is_none() {
+            if let Err(err) = myself
+                .writer
+                .write_all(&buffer)
+                .and_then(|_| myself.writer.flush())
+            {
+                myself.error = Some(err);
+            }
+        }
+    }
+}
+
+/// A listener callback, shared so it can keep running after its listener is removed.
+type Handler = Rc<RefCell<Box<dyn FnMut(RemoteEvent)>>>;
+
+/// The listener callbacks, indexed by their handler id.
+type EventHandlers = Rc<RefCell<IdSlab<Handler>>>;
+
+#[derive(Default, Clone)]
+struct SharedHandlers {
+    event_handlers: EventHandlers,
+}
+
+/// The most distinct tag, attribute, style and namespace names all [`RemoteClient`]s keep together.
+pub const MAX_NAMES: usize = 4096;
+
+/// The longest tag, attribute, style or namespace name a [`RemoteClient`] accepts.
+pub const MAX_NAME_LEN: usize = 256;
+
+/// Applies the ops sent by a [`RemoteRenderer`] to a local renderer and writes the events of that renderer back to `W`.
+pub struct RemoteClient<R, W: Write> {
+    ui: R,
+    // maps the ids of the remote renderer to ids of the local renderer
+    ids: HashMap<u32, u32>,
+    // the start of an op that has not fully arrived yet
+    pending: Vec<u8>,
+    events: Rc<RefCell<EventWriter<W>>>,
+}
+
+struct EventWriter<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> EventWriter<W> {
+    fn send(&mut self, event: RemoteEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut bytes = Vec::new();
+        event.encode(&mut bytes);
+        if let Err(err) = self
+            .writer
+            .write_all(&bytes)
+            .and_then(|_| self.writer.flush())
+        {
+            self.error = Some(err);
+        }
+    }
+}
+
+impl<R: Renderer<R> + PlatformEvents, W: Write + 'static> RemoteClient<R, W> {
+    pub fn new(ui: R, events: W) -> Self {
+        Self {
+            ui,
+            ids: HashMap::from([(0, 0)]),
+            pending: Vec::new(),
+            events: Rc::new(RefCell::new(EventWriter {
+                writer: events,
+                error: None,
+            })),
+        }
+    }
+
+    pub fn ui(&self) -> &R {
+        &self.ui
+    }
+
+    /// Apply every complete op in `bytes`.
+    ///
+    /// The bytes may end in the middle of an op, the rest of it is expected in the next call. Returns the first error writing events since the last call.
+    pub fn apply(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let pending = std::mem::take(&mut self.pending);
+        let mut rest = pending.as_slice();
+        loop {
+            let mut next = rest;
+            match Op::decode(&mut next) {
+                Ok(op) => {
+                    rest = next;
+                    self.apply_op(op)?;
+                }
+                Err(DecodeError::UnexpectedEnd) => break,
+                Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            }
+        }
+        self.pending = rest.to_vec();
+
+        match self.events.borrow_mut().error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn local(&self, id: u32) -> io::Result<u32> {
+        self.ids.get(&id).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("node {id} was never created"),
+            )
+        })
+    }
+
+    fn apply_op(&mut self, op: Op) -> io::Result<()> {
+        match op {
+            Op::Node { id } => {
+                let local = self.ui.node();
+                self.ids.insert(id, local);
+            }
+            Op::SetAttribute { id, name, value } => {
+                let name = intern(name)?;
+                self.ui.set_attribute(self.local(id)?, name, &value);
+            }
+            Op::SetAttributeNs {
+                id,
+                name,
+                namespace,
+                value,
+            } => {
+                let name = intern(name)?;
+                let namespace = intern(namespace)?;
+                self.ui
+                    .set_attribute_ns(self.local(id)?, name, namespace, &value);
+            }
+            Op::RemoveAttribute { id, name } => {
+                let name = intern(name)?;
+                self.ui.remove_attribute(self.local(id)?, name);
+            }
+            Op::SetStyle { id, name, value } => {
+                let name = intern(name)?;
+                self.ui.set_style(self.local(id)?, name, &value);
+            }
+            Op::CreateElement { id, tag } => {
+                let tag = intern(tag)?;
+                self.ui.create_element(self.local(id)?, tag);
+            }
+            Op::CreateElementNs { id, tag, namespace } => {
+                let tag = intern(tag)?;
+                let namespace = intern(namespace)?;
+                self.ui.create_element_ns(self.local(id)?, tag, namespace);
+            }
+            Op::CreateText { id, text } => self.ui.create_text(self.local(id)?, &text),
+            Op::CreateComment { id, text } => self.ui.create_comment(self.local(id)?, &text),
+            Op::SetText { id, text } => self.ui.set_text(self.local(id)?, &text),
+            Op::AppendChild { parent, child } => self
+                .ui
+                .append_child(self.local(parent)?, self.local(child)?),
+            Op::InsertBefore { anchor, id } => {
+                self.ui.insert_before(self.local(anchor)?, self.local(id)?)
+            }
+            Op::MoveBefore { anchor, id } => {
+                self.ui.move_before(self.local(anchor)?, self.local(id)?)
+            }
+            Op::CloneNode { id, new_id } => {
+                self.ui.clone_node(self.local(id)?, self.local(new_id)?)
+            }
+            Op::Copy { from, to } => self.ui.copy(self.local(from)?, self.local(to)?),
+            Op::FirstChild { id } => self.ui.first_child(self.local(id)?),
+            Op::NextSibling { id } => self.ui.next_sibling(self.local(id)?),
+            Op::Remove { id } => self.ui.remove(self.local(id)?),
+            Op::ReturnNode { id } => {
+                let local = self.local(id)?;
+                self.ids.remove(&id);
+                self.ui.return_node(local);
+            }
+            Op::AddListener { id, event } => {
+                let local = self.local(id)?;
+                let listener = Listen {
+                    ui: &mut self.ui,
+                    local,
+                    remote: id,
+                    events: self.events.clone(),
+                };
+                visit_event(&event, listener).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("unknown event {event}"))
+                })?;
+            }
+            Op::Flush => self.ui.flush(),
+        }
+        Ok(())
+    }
+}
+
+/// Get a `&'static str` for a tag, attribute, style or namespace name sent by the peer.
+///
+/// Renderers need names that live forever, so each distinct name is leaked once and shared by every client. There are at most [`MAX_NAMES`] of them, of up to [`MAX_NAME_LEN`] bytes, so a peer cannot make the process leak memory without bound.
+fn intern(name: String) -> io::Result<&'static str> {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let mut names = NAMES.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(name) = names.get(name.as_str()) {
+        return Ok(name);
+    }
+    if name.len() > MAX_NAME_LEN || names.len() >= MAX_NAMES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("too many names or a name that is too long: {name:.32}"),
+        ));
+    }
+    let name = Box::leak(name.into_boxed_str());
+    names.insert(name);
+    Ok(name)
+}
+
+/// Adds a listener to the local renderer that sends the event back to the remote renderer.
+struct Listen<'a, R, W: Write> {
+    ui: &'a mut R,
+    local: u32,
+    remote: u32,
+    events: Rc<RefCell<EventWriter<W>>>,
+}
+
+impl<R: Renderer<R> + PlatformEvents, W: Write + 'static> EventVisitor<R> for Listen<'_, R, W> {
+    type Output = ();
+
+    fn visit<E: EventDescription<R> + 'static>(self, event: E) {
+        let events = self.events;
+        let remote = self.remote;
+        self.ui.add_listener(
+            self.local,
+            event,
+            Box::new(move |_| {
+                events.borrow_mut().send(RemoteEvent {
+                    id: remote,
+                    name: E::NAME.to_string(),
+                })
+            }),
+        );
+    }
+}
+
+#[test]
+fn counter_over_pipe() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Counter(cx: Scope) {
+        let num: Rx<i32> = 0;
+
+        rsx! {
+            <button onclick=|_| *num += 1>
+                "increase"
+            </button>
+            <div>
+                "count: {num}"
+            </div>
+        }
+    }
+
+    /// One end of an in-process pipe.
+    #[derive(Clone, Default)]
+    struct Pipe(Rc<RefCell<Vec<u8>>>);
+
+    impl Pipe {
+        fn take(&self) -> Vec<u8> {
+            std::mem::take(&mut self.0.borrow_mut())
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let (ops, events) = (Pipe::default(), Pipe::default());
+    let server = RemoteRenderer::new(ops.clone());
+    launch(server.clone(), Counter {});
+
+    let ui = TestRenderer::default();
+    let mut client = RemoteClient::new(ui.clone(), events.clone());
+    // ops may be split anywhere in the stream
+    let bytes = ops.take();
+    let (first, second) = bytes.split_at(bytes.len() / 2);
+    client.apply(first).unwrap();
+    client.apply(second).unwrap();
+
+    let button = ui.dom().find_all("button")[0];
+    let div = ui.dom().find_all("div")[0];
+    assert_eq!(ui.dom().text_content(div), "count: 0");
+
+    ui.dispatch(button, crate::events::click);
+    ui.dispatch(button, crate::events::click);
+    server.handle_events(&events.take()).unwrap();
+    client.apply(&ops.take()).unwrap();
+    assert_eq!(ui.dom().text_content(div), "count: 2");
+}
+
+#[test]
+fn removed_listeners_do_not_run_for_reused_ids() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    thread_local! {
+        static CLICKED: RefCell<Vec<i32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[component]
+    fn Rows(cx: Scope) {
+        let items: Rx<Vec<i32>> = vec![1, 2];
+
+        rsx! {
+            <for item in {items.iter().copied()}>
+                <button onclick=|_| {
+                    CLICKED.with(|clicked| clicked.borrow_mut().push(item));
+                    items.retain(|i| *i != item);
+                }>"remove"</button>
+            </for>
+            <p onclick=|_| items.push(3)>"add"</p>
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Pipe(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let (ops, events) = (Pipe::default(), Pipe::default());
+    let server = RemoteRenderer::new(ops.clone());
+    launch(server.clone(), Rows {});
+    let ui = TestRenderer::default();
+    let mut client = RemoteClient::new(ui.clone(), events.clone());
+    client.apply(&ops.0.take()).unwrap();
+    let mut click = |node| {
+        ui.dispatch(node, crate::events::click);
+        server.handle_events(&events.0.take()).unwrap();
+        client.apply(&ops.0.take()).unwrap();
+    };
+
+    // the row removes itself while its listener runs, then the new row reuses its id
+    let second = ui.dom().find_all("button")[1];
+    click(second);
+    let add = ui.dom().find_all("p")[0];
+    click(add);
+    let buttons = ui.dom().find_all("button");
+    assert_eq!(buttons.len(), 2);
+    click(buttons[1]);
+    assert_eq!(CLICKED.with(|clicked| clicked.borrow().clone()), [2, 3]);
+}
+
+#[test]
+fn rejects_long_names() {
+    use crate::testing::TestRenderer;
+
+    let mut bytes = Vec::new();
+    Op::Node { id: 1 }.encode(&mut bytes);
+    Op::CreateElement {
+        id: 1,
+        tag: "x".repeat(MAX_NAME_LEN + 1),
+    }
+    .encode(&mut bytes);
+
+    let mut client = RemoteClient::new(TestRenderer::default(), Vec::new());
+    let err = client.apply(&bytes).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -1,3 +1,7 @@
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+
 use crate::events::{EventDescription, PlatformEvents};
 
 pub trait Renderer<P: PlatformEvents>: Sized {
@@ -7,16 +11,41 @@ pub trait Renderer<P: PlatformEvents>: Sized {
 
     fn set_attribute(&mut self, id: u32, name: &'static str, value: &str);
 
+    /// Set an attribute like `xlink:href` that belongs to a namespace.
+    fn set_attribute_ns(
+        &mut self,
+        id: u32,
+        name: &'static str,
+        namespace: &'static str,
+        value: &str,
+    );
+
+    fn remove_attribute(&mut self, id: u32, name: &'static str);
+
     fn set_style(&mut self, id: u32, name: &'static str, value: &str);
 
     fn create_element(&mut self, id: u32, tag: &'static str);
 
+    /// Create an element like `svg` or `math` that is not an HTML element.
+    fn create_element_ns(&mut self, id: u32, tag: &'static str, namespace: &'static str);
+
     fn create_text(&mut self, id: u32, text: &str);
 
+    /// Create a comment node. Comments are not rendered, they show up in server rendered HTML and can be used as anchors.
+    fn create_comment(&mut self, id: u32, text: &str);
+
     fn set_text(&mut self, id: u32, text: &str);
 
     fn append_child(&mut self, parent: u32, child: u32);
 
+    /// Move `id` in front of `anchor`, under the same parent.
+    fn insert_before(&mut self, anchor: u32, id: u32);
+
+    /// Move `id`, which is already in the same parent as `anchor`, in front of `anchor`. Renderers that can should keep state like focus and scroll position of the moved node, the others insert it again.
+    fn move_before(&mut self, anchor: u32, id: u32) {
+        self.insert_before(anchor, id)
+    }
+
     fn clone_node(&mut self, id: u32, new_id: u32);
 
     fn copy(&mut self, from: u32, to: u32);
@@ -33,12 +62,58 @@ pub trait Renderer<P: PlatformEvents>: Sized {
         &mut self,
         id: u32,
         event: E,
-        callback: Box<dyn FnMut(web_sys::Event)>,
+        callback: Box<dyn FnMut(P::Event)>,
     );
 
+    /// Get the roots of a template this renderer has already created.
+    fn get_template(&mut self, key: TypeId) -> Option<Vec<u32>>;
+
+    /// Remember the roots of a template so later instances can clone it.
+    fn set_template(&mut self, key: TypeId, roots: Vec<u32>);
+
+    /// Set the `<!DOCTYPE>` of the document. Only renderers that write a whole document, like the SSR renderer, use it.
+    fn set_doctype(&mut self, _doctype: &str) {}
+
     fn flush(&mut self) {}
+
+    /// Called before a component starts creating its nodes.
+    fn start_component(&mut self) {}
+
+    /// Called once a component and everything inside it is created.
+    fn end_component(&mut self) {}
+
+    /// Called while a component is created with the roots at the start of it that are finished, they are not changed again before the component is created.
+    ///
+    /// Renderers that write the page as it is built, like the streaming SSR renderer, use it to send the roots of the launched component early.
+    fn roots_finished(&mut self, _roots: &[u32]) {}
 }
 
+/// A renderer that can take over nodes rendered on the server, used by [`crate::hydrate`].
+pub trait Hydrate {
+    /// Until [`Hydrate::finish_hydration`] is called, template clones and the nodes found inside them with `first_child` and `next_sibling` are stand-ins that record what is done to them instead of new nodes.
+    fn start_hydration(&mut self);
+
+    /// Match the stand-ins, starting from the `roots` of the hydrated component, with the server rendered children of the root and point their ids at the server rendered nodes.
+    ///
+    /// If the server rendered nodes have a different shape, the page is left as the server rendered it and the ids of the stand-ins point at nothing.
+    fn finish_hydration(&mut self, roots: &[u32]) -> Result<(), HydrationError>;
+}
+
+/// The error returned when the server rendered nodes do not match the hydrated component, for example because the HTML was cached before the component changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HydrationError {
+    /// The position of the first node that did not match, as the child indexes from the root.
+    pub path: Vec<usize>,
+}
+
+impl Display for HydrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the server rendered a different node at {:?}", self.path)
+    }
+}
+
+impl std::error::Error for HydrationError {}
+
 impl<'a, R: Renderer<R> + PlatformEvents + Sized> Renderer<R> for &'a mut R {
     fn node(&mut self) -> u32 {
         R::node(self)
@@ -52,6 +127,20 @@ impl<'a, R: Renderer<R> + PlatformEvents + Sized> Renderer<R> for &'a mut R {
         R::set_attribute(self, id, name, value)
     }
 
+    fn set_attribute_ns(
+        &mut self,
+        id: u32,
+        name: &'static str,
+        namespace: &'static str,
+        value: &str,
+    ) {
+        R::set_attribute_ns(self, id, name, namespace, value)
+    }
+
+    fn remove_attribute(&mut self, id: u32, name: &'static str) {
+        R::remove_attribute(self, id, name)
+    }
+
     fn set_style(&mut self, id: u32, name: &'static str, value: &str) {
         R::set_style(self, id, name, value)
     }
@@ -60,10 +149,18 @@ impl<'a, R: Renderer<R> + PlatformEvents + Sized> Renderer<R> for &'a mut R {
         R::create_element(self, id, tag)
     }
 
+    fn create_element_ns(&mut self, id: u32, tag: &'static str, namespace: &'static str) {
+        R::create_element_ns(self, id, tag, namespace)
+    }
+
     fn create_text(&mut self, id: u32, text: &str) {
         R::create_text(self, id, text)
     }
 
+    fn create_comment(&mut self, id: u32, text: &str) {
+        R::create_comment(self, id, text)
+    }
+
     fn set_text(&mut self, id: u32, text: &str) {
         R::set_text(self, id, text)
     }
@@ -72,6 +169,14 @@ impl<'a, R: Renderer<R> + PlatformEvents + Sized> Renderer<R> for &'a mut R {
         R::append_child(self, parent, child)
     }
 
+    fn insert_before(&mut self, anchor: u32, id: u32) {
+        R::insert_before(self, anchor, id)
+    }
+
+    fn move_before(&mut self, anchor: u32, id: u32) {
+        R::move_before(self, anchor, id)
+    }
+
     fn clone_node(&mut self, id: u32, new_id: u32) {
         R::clone_node(self, id, new_id)
     }
@@ -100,12 +205,90 @@ impl<'a, R: Renderer<R> + PlatformEvents + Sized> Renderer<R> for &'a mut R {
         &mut self,
         id: u32,
         event: E,
-        callback: Box<dyn FnMut(web_sys::Event)>,
+        callback: Box<dyn FnMut(R::Event)>,
     ) {
         R::add_listener(self, id, event, callback)
     }
 
+    fn get_template(&mut self, key: TypeId) -> Option<Vec<u32>> {
+        R::get_template(self, key)
+    }
+
+    fn set_template(&mut self, key: TypeId, roots: Vec<u32>) {
+        R::set_template(self, key, roots)
+    }
+
+    fn set_doctype(&mut self, doctype: &str) {
+        R::set_doctype(self, doctype)
+    }
+
     fn flush(&mut self) {
         R::flush(self)
     }
+
+    fn start_component(&mut self) {
+        R::start_component(self)
+    }
+
+    fn end_component(&mut self) {
+        R::end_component(self)
+    }
+
+    fn roots_finished(&mut self, roots: &[u32]) {
+        R::roots_finished(self, roots)
+    }
+}
+
+/// A value that can be used for a dynamic attribute.
+///
+/// `bool` and `Option` values add the attribute when they are `true` or `Some` and remove it otherwise.
+pub trait AttributeValue {
+    /// The text of the attribute or `None` if the attribute should be removed.
+    fn value(&self) -> Option<Cow<'_, str>>;
+}
+
+impl AttributeValue for str {
+    fn value(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl AttributeValue for String {
+    fn value(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl AttributeValue for bool {
+    fn value(&self) -> Option<Cow<'_, str>> {
+        self.then_some(Cow::Borrowed(""))
+    }
 }
+
+impl<T: AttributeValue> AttributeValue for Option<T> {
+    fn value(&self) -> Option<Cow<'_, str>> {
+        self.as_ref()?.value()
+    }
+}
+
+impl<T: AttributeValue + ?Sized> AttributeValue for &T {
+    fn value(&self) -> Option<Cow<'_, str>> {
+        T::value(self)
+    }
+}
+
+macro_rules! display_attribute_value {
+    ($($ty:ty),*) => {
+        $(
+            impl AttributeValue for $ty {
+                fn value(&self) -> Option<Cow<'_, str>> {
+                    Some(Cow::Owned(self.to_string()))
+                }
+            }
+        )*
+    };
+}
+
+display_attribute_value!(
+    char, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
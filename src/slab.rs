@@ -35,7 +35,15 @@ impl<T> IdSlab<T> {
         self.data[id as usize] = None;
     }
 
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.data.get(id as usize)?.as_ref()
+    }
+
     pub fn get_mut(&mut self, id: u32) -> Option<&mut T> {
         self.data[id as usize].as_mut()
     }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data.iter_mut().flatten()
+    }
 }
@@ -0,0 +1,392 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::dom::{Dom, DomBackend, DomRenderer, NodeId};
+use crate::events::EventDescription;
+
+/// A renderer that builds HTML on the server.
+///
+/// Listeners are dropped because nothing can trigger them.
+pub type SsrRenderer = DomRenderer<SsrBackend>;
+
+#[derive(Default)]
+pub struct SsrBackend {
+    doctype: Option<String>,
+}
+
+impl SsrRenderer {
+    /// The HTML for everything that was appended to the root so far, after the doctype if a component set one.
+    pub fn html(&self) -> String {
+        let myself = self.0.borrow();
+        let html = myself.dom.inner_html(myself.dom.root());
+        match &myself.backend.doctype {
+            Some(doctype) => format!("<!DOCTYPE {doctype}>{html}"),
+            None => html,
+        }
+    }
+}
+
+impl DomBackend for SsrBackend {
+    type Event = ();
+
+    fn add_listener<E: EventDescription<SsrRenderer>>(
+        &mut self,
+        _: &mut Dom,
+        _: u32,
+        _: E,
+        _: Box<dyn FnMut(())>,
+    ) {
+    }
+
+    fn set_doctype(&mut self, doctype: &str) {
+        self.doctype = Some(doctype.to_string());
+    }
+}
+
+/// A renderer that writes the page to `W` while it is built.
+///
+/// Each root of the launched component is written and flushed as soon as it is finished, which is after the child components inside it are created, so a server can send the start of the page before the rest is built.
+pub type StreamingRenderer<W> = DomRenderer<StreamingBackend<W>>;
+
+pub struct StreamingBackend<W: Write> {
+    writer: W,
+    buffer: String,
+    after_text: bool,
+    error: Option<io::Error>,
+    // the number of components being created, the launched component is the first
+    depth: usize,
+    // roots written before the launched component appended them to the root node
+    written: HashSet<NodeId>,
+}
+
+impl<W: Write> StreamingRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        DomRenderer::with_backend(StreamingBackend {
+            writer,
+            buffer: String::new(),
+            after_text: false,
+            error: None,
+            depth: 0,
+            written: HashSet::new(),
+        })
+    }
+
+    /// Get the writer back along with the first error writing to it.
+    ///
+    /// Panics if a component still holds a clone of the renderer.
+    pub fn finish(self) -> io::Result<W> {
+        let inner = Rc::try_unwrap(self.0)
+            .ok()
+            .expect("the renderer is still used by a component")
+            .into_inner()
+            .backend;
+        match inner.error {
+            Some(err) => Err(err),
+            None => Ok(inner.writer),
+        }
+    }
+}
+
+impl<W: Write> StreamingBackend<W> {
+    fn stream(&mut self, dom: &mut Dom, id: u32) {
+        if self.error.is_some() {
+            return;
+        }
+        let node = dom.lookup(id).expect("streamed a missing node");
+        self.buffer.clear();
+        dom.write_sibling(node, &mut self.after_text, &mut self.buffer)
+            .expect("writing to a string cannot fail");
+        if let Err(err) = self.writer.write_all(self.buffer.as_bytes()) {
+            self.error = Some(err);
+        }
+        // the root is never read again once it is written
+        dom.remove(id);
+    }
+}
+
+impl<W: Write> DomBackend for StreamingBackend<W> {
+    type Event = ();
+
+    fn add_listener<E: EventDescription<StreamingRenderer<W>>>(
+        &mut self,
+        _: &mut Dom,
+        _: u32,
+        _: E,
+        _: Box<dyn FnMut(())>,
+    ) {
+    }
+
+    fn append_to_root(&mut self, dom: &mut Dom, id: u32) {
+        let node = dom.lookup(id).expect("streamed a missing node");
+        if self.written.remove(&node) {
+            dom.remove(id);
+            return;
+        }
+        self.stream(dom, id);
+    }
+
+    fn start_component(&mut self) {
+        self.depth += 1;
+    }
+
+    fn end_component(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn roots_finished(&mut self, dom: &mut Dom, roots: &[u32]) {
+        if self.depth != 1 {
+            return;
+        }
+        let mut streamed = false;
+        for &id in roots {
+            let node = dom.lookup(id).expect("streamed a missing node");
+            if self.written.insert(node) {
+                dom.append_child(0, id);
+                self.stream(dom, id);
+                streamed = true;
+            }
+        }
+        if streamed {
+            self.flush();
+        }
+    }
+
+    // the doctype is set while the component is created, before any root is written
+    fn set_doctype(&mut self, doctype: &str) {
+        if self.error.is_none() {
+            if let Err(err) = write!(self.writer, "<!DOCTYPE {doctype}>") {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.error.is_none() {
+            if let Err(err) = self.writer.flush() {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+#[test]
+fn renders_component() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Greeting(cx: Scope, name: &'static str) {
+        rsx! {
+            <p class="greeting">
+                "Hello, {name}!"
+            </p>
+        }
+    }
+
+    assert_eq!(
+        render_to_string(Greeting {
+            name: "<Ferris & co>"
+        }),
+        r#"<p class="greeting">Hello, &lt;Ferris &amp; co&gt;!</p>"#
+    );
+}
+
+#[test]
+fn streams_roots() {
+    use crate::prelude::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct LogWriter;
+
+    impl Write for LogWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let written = String::from_utf8(buf.to_vec()).unwrap();
+            LOG.with(|log| log.borrow_mut().push(written));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            LOG.with(|log| log.borrow_mut().push("flush".to_string()));
+            Ok(())
+        }
+    }
+
+    #[component]
+    fn Body(cx: Scope) {
+        // the number of writes and flushes before this component was created
+        let sent: Rx<usize> = LOG.with(|log| log.borrow().len());
+
+        rsx! {
+            <p>"{sent}"</p>
+        }
+    }
+
+    #[component]
+    fn Page(cx: Scope, title: &'static str) {
+        rsx! {
+            <h1>"{title}"</h1>
+            <Body />
+            <p>"end"</p>
+        }
+    }
+
+    render_to_writer(Page { title: "a & b" }, LogWriter).unwrap();
+    // the heading is sent before `Body` is created, the rest once `Body` finishes
+    assert_eq!(
+        LOG.with(|log| log.borrow().clone()),
+        [
+            "<h1>a &amp; b</h1>",
+            "flush",
+            "<p>2</p>",
+            "<!--t-->",
+            "<p>end</p>",
+            "flush",
+            "flush"
+        ]
+    );
+}
+
+#[test]
+fn renders_documents() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Document(cx: Scope) {
+        rsx! {
+            <!DOCTYPE html>
+            <html>
+                <body>
+                    <!-- "fragments add their children in place" -->
+                    <>
+                        <p>"a"</p>
+                        <p>"b"</p>
+                    </>
+                </body>
+            </html>
+        }
+    }
+
+    let expected = "<!DOCTYPE html><html><body><!--fragments add their children in place--><p>a</p><p>b</p></body></html>";
+    assert_eq!(render_to_string(Document {}), expected);
+    let html = render_to_writer(Document {}, Vec::new()).unwrap();
+    assert_eq!(String::from_utf8(html).unwrap(), expected);
+}
+
+#[test]
+fn hydrates_server_html() {
+    use crate::prelude::*;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn Counter(cx: Scope) {
+        let count: Rx<i32> = 0;
+
+        rsx! {
+            <button onclick=|_| *count += 1>"{count}"</button>
+        }
+    }
+
+    #[component]
+    fn App(cx: Scope) {
+        let items: Rx<Vec<i32>> = vec![1, 2];
+
+        rsx! {
+            <h1>"items"</h1>
+            <ul>
+                <for item in {items.iter().copied()}>
+                    <li onclick=|_| items.retain(|i| *i != item)>"{item}"</li>
+                </for>
+            </ul>
+            <Counter />
+            <div>
+                <Counter />
+                <if {items.len() > 2}>
+                    <b>"many"</b>
+                </if>
+            </div>
+            <p onclick=|_| items.push(3)>"add"</p>
+        }
+    }
+
+    let html = render_to_string(App {});
+    assert_eq!(
+        html,
+        "<h1>items</h1><ul><li>1</li><li>2</li><!--t--></ul><button>0</button><!--t--><div><button>0</button><!--t--><!--t--></div><p>add</p>"
+    );
+
+    let ui = TestRenderer::with_html(&html);
+    let server = ui.dom().get(ui.dom().root()).children().to_vec();
+    let items = ui.dom().find_all("li");
+    let buttons = ui.dom().find_all("button");
+    hydrate(ui.clone(), App {}).unwrap();
+
+    // the server rendered nodes are kept instead of being replaced
+    let dom = ui.dom();
+    assert_eq!(dom.get(dom.root()).children(), server);
+    assert_eq!(dom.find_all("li"), items);
+    assert_eq!(dom.find_all("button"), buttons);
+    assert_eq!(dom.text_content(dom.root()), "items1200add");
+    drop(dom);
+
+    // the listeners and updates of the components and the regions reach the server rendered nodes
+    ui.dispatch(buttons[0], crate::events::click);
+    ui.dispatch(buttons[1], crate::events::click);
+    ui.dispatch(buttons[1], crate::events::click);
+    assert_eq!(ui.dom().text_content(buttons[0]), "1");
+    assert_eq!(ui.dom().text_content(buttons[1]), "2");
+
+    let p = ui.dom().find_all("p")[0];
+    ui.dispatch(p, crate::events::click);
+    let div = ui.dom().find_all("div")[0];
+    assert_eq!(ui.dom().text_content(div), "2many");
+    ui.dispatch(items[0], crate::events::click);
+    let ul = ui.dom().find_all("ul")[0];
+    assert_eq!(ui.dom().text_content(ul), "23");
+    // the unkeyed loop reuses the server rendered row for the remaining item
+    assert_eq!(ui.dom().get(ul).children()[0], items[0]);
+}
+
+#[test]
+fn reports_hydration_mismatches() {
+    use crate::prelude::*;
+    use crate::renderer::HydrationError;
+    use crate::testing::TestRenderer;
+
+    #[component]
+    fn List(cx: Scope) {
+        let items: Rx<Vec<i32>> = vec![1, 2];
+
+        rsx! {
+            <ul>
+                <for item in {items.iter()}>
+                    <li>"{item}"</li>
+                </for>
+            </ul>
+            <button onclick=|_| items.push(3)>"add"</button>
+        }
+    }
+
+    // HTML cached before the list or the button changed
+    let stale = [
+        (
+            "<ul><li>1</li><!--t--></ul><button>add</button>",
+            vec![0, 1],
+        ),
+        ("<ul><li>1</li><li>2</li><!--t--></ul><p>add</p>", vec![1]),
+        ("<ul><li>1</li><li>2</li><!--t--></ul>", vec![1]),
+        (
+            "<ul><li>1</li><li>2</li><!--t--></ul><button>add</button><p>more</p>",
+            vec![2],
+        ),
+    ];
+    for (html, path) in stale {
+        let ui = TestRenderer::with_html(html);
+        assert_eq!(hydrate(ui.clone(), List {}), Err(HydrationError { path }));
+        // the page is left as the server rendered it, without listeners
+        assert_eq!(ui.dom().inner_html(ui.dom().root()), html);
+    }
+}
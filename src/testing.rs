@@ -0,0 +1,487 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::component::Component;
+use crate::dom::{Attribute, Dom, DomBackend, DomRenderer, NodeId, NodeKind};
+use crate::events::EventDescription;
+use crate::slab::IdSlab;
+
+/// A renderer that keeps the whole tree in memory so components can be tested without a browser.
+pub type TestRenderer = DomRenderer<TestBackend>;
+
+#[derive(Default)]
+pub struct TestBackend {
+    direct_listeners: Vec<(NodeId, &'static str, u32)>,
+    event_handlers: SharedHandlers,
+}
+
+/// The event type every [`TestRenderer`] event resolves to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestEvent {
+    pub name: &'static str,
+    pub target: NodeId,
+}
+
+impl TestRenderer {
+    /// A renderer whose root already holds server rendered HTML, to test [`crate::hydrate`].
+    pub fn with_html(html: &str) -> Self {
+        let ui = Self::default();
+        {
+            let mut myself = ui.0.borrow_mut();
+            let root = myself.dom.root();
+            myself.dom.append_html(root, html);
+        }
+        ui
+    }
+
+    /// Launch a component in a new renderer.
+    pub fn launch<C: Component<Self, Self>>(props: C) -> Self {
+        let ui = Self::default();
+        crate::launch(ui.clone(), props);
+        ui
+    }
+
+    /// The element with the tag at `idx` in document order.
+    pub fn find(&self, tag: &str, idx: usize) -> NodeId {
+        let found = self.dom().find_all(tag);
+        match found.get(idx) {
+            Some(node) => *node,
+            None => panic!("there are only {} <{tag}> elements", found.len()),
+        }
+    }
+
+    /// The concatenated text of a node and all of its descendants.
+    pub fn text(&self, node: NodeId) -> String {
+        self.dom().text_content(node)
+    }
+
+    pub fn click(&self, target: NodeId) {
+        self.dispatch(target, crate::events::click);
+    }
+
+    /// Run the listeners for an event the same way the delegated listeners in the web renderer do.
+    ///
+    /// Bubbling events run every handler from `target` up to the root, other events only run the handlers attached directly to `target`.
+    pub fn dispatch<E: EventDescription<TestRenderer>>(&self, target: NodeId, _: E) {
+        // clone the handlers out so they can use the renderer, and remove their own node, while they run
+        let handlers: Vec<Handler> = {
+            let myself = self.0.borrow();
+            let mut handlers = Vec::new();
+            if E::BUBBLES {
+                let attribute = format!("data-event-{}", E::ID);
+                let mut node = Some(target);
+                while let Some(current) = node {
+                    let current = myself.dom.get(current);
+                    if let Some(handler_id) = current
+                        .attribute(&attribute)
+                        .and_then(|handler_id| handler_id.parse::<u32>().ok())
+                    {
+                        handlers.push(handler_id);
+                    }
+                    node = current.parent();
+                }
+            } else {
+                handlers.extend(
+                    myself
+                        .backend
+                        .direct_listeners
+                        .iter()
+                        .filter(|(node, name, _)| *node == target && *name == E::NAME)
+                        .map(|(_, _, handler_id)| *handler_id),
+                );
+            }
+            let event_handlers = myself.backend.event_handlers.event_handlers.borrow();
+            handlers
+                .into_iter()
+                .map(|handler_id| {
+                    event_handlers
+                        .get(handler_id)
+                        .expect("handler not found")
+                        .clone()
+                })
+                .collect()
+        };
+
+        for handler in handlers {
+            (handler.borrow_mut())(TestEvent {
+                name: E::NAME,
+                target,
+            });
+        }
+    }
+}
+
+impl DomBackend for TestBackend {
+    type Event = TestEvent;
+
+    fn add_listener<E: EventDescription<TestRenderer>>(
+        &mut self,
+        dom: &mut Dom,
+        id: u32,
+        _: E,
+        callback: Box<dyn FnMut(TestEvent)>,
+    ) {
+        let handler_id =
+            (self.event_handlers.event_handlers.borrow_mut()).id(Rc::new(RefCell::new(callback)));
+
+        if E::BUBBLES {
+            // mirror the attribute the web renderer uses to find delegated handlers
+            dom.set_attribute(
+                id,
+                &format!("data-event-{}", E::ID),
+                &handler_id.to_string(),
+            );
+        } else {
+            let node = dom.lookup(id).expect("listener added to a missing node");
+            self.direct_listeners.push((node, E::NAME, handler_id));
+        }
+    }
+
+    fn hydrated(&mut self, adopted: &HashMap<NodeId, NodeId>) {
+        for (node, _, _) in &mut self.direct_listeners {
+            if let Some(server) = adopted.get(node) {
+                *node = *server;
+            }
+        }
+    }
+
+    fn freed(&mut self, dom: &Dom, nodes: &[NodeId]) {
+        let mut handlers = self.event_handlers.event_handlers.borrow_mut();
+        for node in nodes {
+            // the delegated listeners of the node
+            if let NodeKind::Element { attributes, .. } = dom.get(*node).kind() {
+                for Attribute { name, value, .. } in attributes {
+                    if name.starts_with("data-event-") {
+                        handlers.recycle(value.parse().expect("invalid handler id"));
+                    }
+                }
+            }
+        }
+        self.direct_listeners.retain(|(node, _, handler_id)| {
+            let freed = nodes.contains(node);
+            if freed {
+                handlers.recycle(*handler_id);
+            }
+            !freed
+        });
+    }
+}
+
+/// A listener callback, shared so it can keep running after its listener is removed.
+type Handler = Rc<RefCell<Box<dyn FnMut(TestEvent)>>>;
+
+/// The listener callbacks, indexed by their handler id.
+type EventHandlers = Rc<RefCell<IdSlab<Handler>>>;
+
+#[derive(Default, Clone)]
+struct SharedHandlers {
+    event_handlers: EventHandlers,
+}
+
+#[test]
+fn counter() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Counter(cx: Scope) {
+        let num: Rx<i32> = 0;
+
+        rsx! {
+            <button onclick=|_| *num += 1>
+                "increase"
+            </button>
+            <div>
+                "count: {num}"
+            </div>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Counter {});
+    // a second instance must not reuse the first renderer's template ids
+    launch(TestRenderer::default(), Counter {});
+
+    let button = ui.dom().find_all("button")[0];
+    let div = ui.dom().find_all("div")[0];
+    assert_eq!(ui.dom().text_content(div), "count: 0");
+
+    ui.dispatch(button, crate::events::click);
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().text_content(div), "count: 2");
+}
+
+#[test]
+fn many_states() {
+    use crate::prelude::*;
+
+    // more than eight states need a wider tracking integer
+    #[component]
+    fn Many(cx: Scope) {
+        let a: Rx<i32> = 0;
+        let b: Rx<i32> = 1;
+        let c: Rx<i32> = 2;
+        let d: Rx<i32> = 3;
+        let e: Rx<i32> = 4;
+        let f: Rx<i32> = 5;
+        let g: Rx<i32> = 6;
+        let h: Rx<i32> = 7;
+        let i: Rx<i32> = 8;
+        let j: Rx<i32> = 9;
+
+        rsx! {
+            <button onclick=|_| *j += 1>
+                "increase"
+            </button>
+            <div>
+                "{a}{b}{c}{d}{e}{f}{g}{h}{i} {j}"
+            </div>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Many {});
+
+    let button = ui.dom().find_all("button")[0];
+    let div = ui.dom().find_all("div")[0];
+    assert_eq!(ui.dom().text_content(div), "012345678 9");
+
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().text_content(div), "012345678 10");
+}
+
+#[test]
+fn typed_memos() {
+    use crate::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    #[component]
+    fn Derived(cx: Scope) {
+        let num: Rx<i32> = 1;
+        let doubled: Memo<i32> = rx(|| *num * 2);
+        let big: Memo<bool> = rx(|| *doubled > 4);
+        rx(|| {
+            let _ = *big;
+            RUNS.fetch_add(1, Ordering::SeqCst);
+        });
+
+        rsx! {
+            <button onclick=|_| *num += 1 title="{doubled}">
+                "{doubled} {big}"
+            </button>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Derived {});
+
+    let button = ui.dom().find_all("button")[0];
+    assert_eq!(ui.dom().text_content(button), "2 false");
+    assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+
+    // `big` stays false, so the memo that reads it does not run again
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().text_content(button), "4 false");
+    assert_eq!(ui.dom().get(button).attribute("title"), Some("4"));
+    assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().text_content(button), "6 true");
+    assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn uncompared_memos() {
+    use crate::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    // not `PartialEq`, so memos of it can't be compared
+    struct Parity(bool);
+
+    #[component]
+    fn Derived(cx: Scope) {
+        let num: Rx<i32> = 0;
+        #[no_compare]
+        let parity: Memo<Parity> = rx(|| Parity(*num % 2 == 0));
+        let even: Memo<bool> = #[no_compare]
+        rx(|| parity.0);
+        rx(|| {
+            let _ = *even;
+            RUNS.fetch_add(1, Ordering::SeqCst);
+        });
+
+        rsx! {
+            <button onclick=|_| *num += 2>"{even}"</button>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Derived {});
+
+    let button = ui.dom().find_all("button")[0];
+    assert_eq!(ui.dom().text_content(button), "true");
+    assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+
+    // `even` stays true, but its dependents rerun every time it runs
+    ui.dispatch(button, crate::events::click);
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().text_content(button), "true");
+    assert_eq!(RUNS.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn nested_memos() {
+    use crate::prelude::*;
+    use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+    static OUTER: AtomicUsize = AtomicUsize::new(0);
+    static INNER: AtomicUsize = AtomicUsize::new(0);
+    static SEEN: AtomicI32 = AtomicI32::new(0);
+
+    #[component]
+    fn Watcher(cx: Scope, start: i32) {
+        let num: Rx<i32> = start;
+        let step: Rx<i32> = *num + 1;
+        let watching: Rx<bool> = true;
+        rx(|| {
+            OUTER.fetch_add(1, Ordering::SeqCst);
+            if *watching {
+                rx(|| {
+                    INNER.fetch_add(1, Ordering::SeqCst);
+                    SEEN.store(*num, Ordering::SeqCst);
+                });
+            }
+        });
+
+        rsx! {
+            <button onclick=|_| *num += *step>"{num}"</button>
+            <button onclick=|_| *watching = !*watching>"watch"</button>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Watcher { start: 2 });
+    let runs = || (OUTER.load(Ordering::SeqCst), INNER.load(Ordering::SeqCst));
+
+    let buttons = ui.dom().find_all("button");
+    let (add, toggle) = (buttons[0], buttons[1]);
+    assert_eq!(runs(), (1, 1));
+    assert_eq!(SEEN.load(Ordering::SeqCst), 2);
+
+    // only the inner memo reads `num`
+    ui.dispatch(add, crate::events::click);
+    assert_eq!(ui.dom().text_content(add), "5");
+    assert_eq!(runs(), (1, 2));
+    assert_eq!(SEEN.load(Ordering::SeqCst), 5);
+
+    // the outer memo reruns without creating the inner memo, so it is dropped
+    ui.dispatch(toggle, crate::events::click);
+    assert_eq!(runs(), (2, 2));
+    ui.dispatch(add, crate::events::click);
+    assert_eq!(runs(), (2, 2));
+    assert_eq!(SEEN.load(Ordering::SeqCst), 5);
+
+    ui.dispatch(toggle, crate::events::click);
+    assert_eq!(runs(), (3, 3));
+    assert_eq!(SEEN.load(Ordering::SeqCst), 8);
+    ui.dispatch(add, crate::events::click);
+    assert_eq!(runs(), (3, 4));
+    assert_eq!(SEEN.load(Ordering::SeqCst), 11);
+}
+
+#[test]
+fn svg_namespace() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Icon(cx: Scope) {
+        rsx! {
+            <div>
+                <svg viewBox="0 0 10 10">
+                    <use xlink:href="#dot"></use>
+                    <path d="M0 0L10 10"></path>
+                </svg>
+            </div>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Icon {});
+
+    let dom = ui.dom();
+    let svg = "http://www.w3.org/2000/svg";
+    assert_eq!(dom.get(dom.find_all("div")[0]).namespace(), None);
+    assert_eq!(dom.get(dom.find_all("svg")[0]).namespace(), Some(svg));
+    assert_eq!(dom.get(dom.find_all("path")[0]).namespace(), Some(svg));
+    let link = dom.get(dom.find_all("use")[0]);
+    assert_eq!(link.attribute("xlink:href"), Some("#dot"));
+    assert_eq!(
+        link.attribute_namespace("xlink:href"),
+        Some("http://www.w3.org/1999/xlink")
+    );
+    let icon = dom.get(dom.find_all("svg")[0]);
+    assert_eq!(icon.attribute_namespace("viewBox"), None);
+}
+
+#[test]
+fn optional_attributes() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Submit(cx: Scope) {
+        let busy: Rx<bool> = false;
+
+        rsx! {
+            <button disabled={*busy} title={busy.then_some("working")} onclick=|_| *busy = !*busy>
+                "submit"
+            </button>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), Submit {});
+
+    let button = ui.dom().find_all("button")[0];
+    assert_eq!(ui.dom().get(button).attribute("disabled"), None);
+    assert_eq!(ui.dom().get(button).attribute("title"), None);
+
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().get(button).attribute("disabled"), Some(""));
+    assert_eq!(ui.dom().get(button).attribute("title"), Some("working"));
+
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().get(button).attribute("disabled"), None);
+}
+
+#[test]
+fn fragments_and_comments() {
+    use crate::prelude::*;
+
+    #[component]
+    fn App(cx: Scope) {
+        let count: Rx<i32> = 0;
+
+        rsx! {
+            <!-- "count" -->
+            <>
+                <b>"{count}"</b>
+                <button onclick=|_| *count += 1>"add"</button>
+            </>
+        }
+    }
+
+    let ui = TestRenderer::default();
+    launch(ui.clone(), App {});
+
+    let root = ui.dom().root();
+    let html = ui.dom().inner_html(root);
+    assert!(html.starts_with("<!--count--><b>0</b><button"));
+
+    let button = ui.dom().find_all("button")[0];
+    ui.dispatch(button, crate::events::click);
+    assert_eq!(ui.dom().text_content(root), "1add");
+}
@@ -1,24 +1,108 @@
-use num_traits::PrimInt;
 use std::cell::Cell;
 use std::fmt::{Debug, Display};
 use std::ops::{Deref, DerefMut};
 
+/// A set of states and memos of a component, one bit for each.
+///
+/// Components use the smallest unsigned integer with a bit for each of their states and memos, and a [`BitSet`] once they need more than 128.
+pub trait Bits: Copy + Default {
+    /// The set with only `num` in it.
+    fn bit(num: usize) -> Self;
+
+    fn union(self, other: Self) -> Self;
+
+    fn contains(&self, num: usize) -> bool;
+
+    fn is_empty(&self) -> bool;
+
+    /// The set without `num` in it.
+    fn without(self, num: usize) -> Self;
+}
+
+macro_rules! int_bits {
+    ($($int:ty),*) => {
+        $(
+            impl Bits for $int {
+                fn bit(num: usize) -> Self {
+                    1 << num
+                }
+
+                fn union(self, other: Self) -> Self {
+                    self | other
+                }
+
+                fn contains(&self, num: usize) -> bool {
+                    self & (1 << num) != 0
+                }
+
+                fn is_empty(&self) -> bool {
+                    *self == 0
+                }
+
+                fn without(self, num: usize) -> Self {
+                    self & !(1 << num)
+                }
+            }
+        )*
+    };
+}
+
+int_bits!(u8, u16, u32, u64, u128);
+
+/// A set of `N * 64` bits for components with more than 128 states and memos.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BitSet<const N: usize>([u64; N]);
+
+impl<const N: usize> Default for BitSet<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> Bits for BitSet<N> {
+    fn bit(num: usize) -> Self {
+        let mut words = [0; N];
+        words[num / 64] = 1 << (num % 64);
+        Self(words)
+    }
+
+    fn union(mut self, other: Self) -> Self {
+        for (word, other) in self.0.iter_mut().zip(other.0) {
+            *word |= other;
+        }
+        self
+    }
+
+    fn contains(&self, num: usize) -> bool {
+        self.0[num / 64] & (1 << (num % 64)) != 0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    fn without(mut self, num: usize) -> Self {
+        self.0[num / 64] &= !(1 << (num % 64));
+        self
+    }
+}
+
 #[derive(Default)]
 pub struct DirtyTrackSet<R, W> {
     pub read: Cell<R>,
     pub write: Cell<W>,
 }
 
-impl<R: PrimInt, W: PrimInt> DirtyTrackSet<R, W> {
-    pub fn is_read(&self, num: u8) -> bool {
-        !(self.read.get() & (R::one() << num as usize)).is_zero()
+impl<R: Bits, W: Bits> DirtyTrackSet<R, W> {
+    pub fn is_read(&self, num: usize) -> bool {
+        self.read.get().contains(num)
     }
 
-    pub fn is_write(&self, num: u8) -> bool {
-        !(self.write.get() & (W::one() << num as usize)).is_zero()
+    pub fn is_write(&self, num: usize) -> bool {
+        self.write.get().contains(num)
     }
 
-    pub fn track(&self, num: u8) -> DirtyTrack<R, W> {
+    pub fn track(&self, num: usize) -> DirtyTrack<R, W> {
         DirtyTrack { data: self, num }
     }
 
@@ -27,7 +111,15 @@ impl<R: PrimInt, W: PrimInt> DirtyTrackSet<R, W> {
     }
 
     pub fn reset_read(&self) {
-        self.read.set(R::zero());
+        self.read.set(R::default());
+    }
+
+    /// Run `f` with its own reads, and return them with its result. The reads from before are restored afterwards, so a memo created inside another memo does not add its dependencies to the outer one.
+    pub fn scoped_read<T>(&self, f: impl FnOnce() -> T) -> (T, R) {
+        let outer = self.read.replace(R::default());
+        let result = f();
+        let read = self.read.replace(outer);
+        (result, read)
     }
 
     pub fn get_write(&self) -> W {
@@ -35,27 +127,27 @@ impl<R: PrimInt, W: PrimInt> DirtyTrackSet<R, W> {
     }
 
     pub fn reset_write(&self) {
-        self.write.set(W::zero());
+        self.write.set(W::default());
     }
 }
 
 #[derive(Copy, Clone)]
 pub struct DirtyTrack<'a, R, W> {
     pub data: &'a DirtyTrackSet<R, W>,
-    pub num: u8,
+    pub num: usize,
 }
 
-impl<R: PrimInt, W: PrimInt> DirtyTrack<'_, R, W> {
+impl<R: Bits, W: Bits> DirtyTrack<'_, R, W> {
     fn read(&self) {
         self.data
             .read
-            .set(self.data.read.get() | (R::one() << self.num as usize));
+            .set(self.data.read.get().union(R::bit(self.num)));
     }
 
     fn write(&self) {
         self.data
             .write
-            .set(self.data.write.get() | (W::one() << self.num as usize));
+            .set(self.data.write.get().union(W::bit(self.num)));
     }
 }
 
@@ -64,19 +156,19 @@ pub struct RwTrack<'a, T, R, W> {
     pub tracking: DirtyTrack<'a, R, W>,
 }
 
-impl<T: Display, R: PrimInt, W: PrimInt> Display for RwTrack<'_, T, R, W> {
+impl<T: Display, R: Bits, W: Bits> Display for RwTrack<'_, T, R, W> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.deref())
     }
 }
 
-impl<T: Debug, R: PrimInt, W: PrimInt> Debug for RwTrack<'_, T, R, W> {
+impl<T: Debug, R: Bits, W: Bits> Debug for RwTrack<'_, T, R, W> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self.deref())
     }
 }
 
-impl<T, R: PrimInt, W: PrimInt> Deref for RwTrack<'_, T, R, W> {
+impl<T, R: Bits, W: Bits> Deref for RwTrack<'_, T, R, W> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -85,7 +177,7 @@ impl<T, R: PrimInt, W: PrimInt> Deref for RwTrack<'_, T, R, W> {
     }
 }
 
-impl<T, R: PrimInt, W: PrimInt> DerefMut for RwTrack<'_, T, R, W> {
+impl<T, R: Bits, W: Bits> DerefMut for RwTrack<'_, T, R, W> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.tracking.write();
         self.data
@@ -134,8 +226,81 @@ fn rw_track() {
     }
 }
 
-pub struct Effect<F, T> {
+#[test]
+fn wide_tracking() {
+    let tracking: DirtyTrackSet<BitSet<3>, BitSet<3>> = DirtyTrackSet::default();
+    let mut value = 0;
+
+    {
+        let mut value = RwTrack {
+            data: &mut value,
+            tracking: tracking.track(130),
+        };
+        *value = 1;
+    }
+
+    assert!(tracking.is_write(130));
+    assert!(!tracking.is_write(2));
+    tracking.reset_write();
+    assert!(tracking.get_write().is_empty());
+}
+
+#[test]
+fn scoped_reads() {
+    let tracking: DirtyTrackSet<u8, u8> = DirtyTrackSet::default();
+    let mut outer = 0;
+    let mut inner = 0;
+
+    let outer = RwTrack {
+        data: &mut outer,
+        tracking: tracking.track(0),
+    };
+    let _ = *outer;
+    let (value, read) = tracking.scoped_read(|| {
+        let inner = RwTrack {
+            data: &mut inner,
+            tracking: tracking.track(1),
+        };
+        *inner + 1
+    });
+
+    assert_eq!(value, 1);
+    assert!(read.contains(1) && !read.contains(0));
+    assert!(tracking.is_read(0) && !tracking.is_read(1));
+    assert_eq!(Bits::without(0b11u8, 0), 0b10);
+}
+
+pub struct Effect<F, T, B> {
     pub rx: F,
-    pub rx_subscriptions: u8,
+    pub rx_subscriptions: B,
     pub current: T,
 }
+
+impl<F, T, B: Bits> Effect<F, T, B> {
+    /// If the effect read the state or memo `num` the last time it ran.
+    pub fn is_subscribed(&self, num: usize) -> bool {
+        self.rx_subscriptions.contains(num)
+    }
+}
+
+impl<F, T: PartialEq, B> Effect<F, T, B> {
+    /// Store the new value of the effect and return if it is different from the old one.
+    pub fn set(&mut self, current: T) -> bool {
+        let changed = self.current != current;
+        self.current = current;
+        changed
+    }
+}
+
+#[test]
+fn effect_changes() {
+    let mut effect = Effect {
+        rx: (),
+        rx_subscriptions: 0u8,
+        current: 1,
+    };
+
+    assert!(!effect.set(1));
+    assert!(effect.set(2));
+    assert_eq!(effect.current, 2);
+}
@@ -0,0 +1,660 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::dom::{Dom, DomBackend, DomRenderer, NodeId, NodeKind};
+use crate::events::EventDescription;
+use crate::slab::IdSlab;
+
+/// Elements that flow inside a line instead of starting a new one.
+const INLINE_ELEMENTS: &[&str] = &["a", "b", "code", "em", "i", "label", "span", "strong"];
+
+/// The width of an input without a `size` attribute.
+const DEFAULT_INPUT_SIZE: usize = 10;
+
+/// A renderer that lays elements out on a grid of characters.
+///
+/// `div` and other block elements start on a new line, `span` and text flow inside the line, `button` is drawn as `[label]` and `input` as `[value    ]`.
+/// Buttons and inputs can be focused with tab or a click, and the focused element is drawn inverted.
+pub type TuiRenderer = DomRenderer<TuiBackend>;
+
+pub struct TuiBackend {
+    listeners: Vec<(NodeId, &'static str, bool, u32)>,
+    event_handlers: SharedHandlers,
+    focused: Option<NodeId>,
+    width: usize,
+    height: usize,
+    // the area each node covered the last time the buffer was drawn
+    boxes: Vec<(NodeId, Rect)>,
+}
+
+/// The event every [`TuiRenderer`] event resolves to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TuiEvent {
+    Key(Key),
+    Mouse { x: usize, y: usize },
+    Input { value: String },
+    Focus,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub inverted: bool,
+}
+
+/// The characters drawn by a [`TuiRenderer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellBuffer {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+impl CellBuffer {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![
+                Cell {
+                    ch: ' ',
+                    inverted: false,
+                };
+                width * height
+            ],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Cell> {
+        (x < self.width && y < self.height).then(|| self.cells[y * self.width + x])
+    }
+
+    /// The characters on a line without trailing spaces, or `None` if the line is below the buffer.
+    pub fn line(&self, y: usize) -> Option<String> {
+        let cells = self.cells.get(y * self.width..(y + 1) * self.width)?;
+        let line: String = cells.iter().map(|cell| cell.ch).collect();
+        Some(line.trim_end().to_string())
+    }
+
+    fn put(&mut self, x: usize, y: usize, ch: char) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x].ch = ch;
+        }
+    }
+
+    fn invert(&mut self, rect: Rect) {
+        for y in rect.y..(rect.y + rect.height).min(self.height) {
+            for x in rect.x..(rect.x + rect.width).min(self.width) {
+                self.cells[y * self.width + x].inverted = true;
+            }
+        }
+    }
+
+    /// Draw the buffer to a terminal using ANSI escape codes.
+    pub fn write_ansi(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "\x1b[H\x1b[2J")?;
+        for y in 0..self.height {
+            let mut inverted = false;
+            for cell in &self.cells[y * self.width..(y + 1) * self.width] {
+                if cell.inverted != inverted {
+                    inverted = cell.inverted;
+                    write!(out, "{}", if inverted { "\x1b[7m" } else { "\x1b[0m" })?;
+                }
+                write!(out, "{}", cell.ch)?;
+            }
+            write!(out, "\x1b[0m\r\n")?;
+        }
+        out.flush()
+    }
+}
+
+impl Display for CellBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in (0..self.height).filter_map(|y| self.line(y)) {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl TuiRenderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        DomRenderer::with_backend(TuiBackend {
+            listeners: Vec::new(),
+            event_handlers: SharedHandlers::default(),
+            focused: None,
+            width,
+            height,
+            boxes: Vec::new(),
+        })
+    }
+
+    pub fn focused(&self) -> Option<NodeId> {
+        self.0.borrow().backend.focused
+    }
+
+    /// Lay out the tree and draw it into a new buffer.
+    pub fn render(&self) -> CellBuffer {
+        let mut myself = self.0.borrow_mut();
+        let mut buffer = CellBuffer::new(myself.backend.width, myself.backend.height);
+        let mut layout = Layout {
+            dom: &myself.dom,
+            buffer: &mut buffer,
+            boxes: Vec::new(),
+            x: 0,
+            y: 0,
+        };
+        let root = layout.dom.root();
+        let width = layout.buffer.width;
+        for child in layout.dom.get(root).children() {
+            layout.node(*child, 0, width);
+        }
+        let boxes = layout.boxes;
+        if let Some((_, rect)) = boxes
+            .iter()
+            .find(|(node, _)| Some(*node) == myself.backend.focused)
+        {
+            buffer.invert(*rect);
+        }
+        myself.backend.boxes = boxes;
+        buffer
+    }
+
+    /// Handle a key press.
+    ///
+    /// Tab moves the focus, enter clicks the focused button and characters edit the focused input. Every key is also sent to the focused element as a `keydown` event.
+    pub fn key(&self, key: Key) {
+        let focused = self.focused();
+        let target = focused.unwrap_or_else(|| self.dom().root());
+        self.dispatch("keydown", target, TuiEvent::Key(key));
+
+        match key {
+            Key::Tab => self.focus_next(),
+            Key::Enter | Key::Char(' ') if self.focused_tag().as_deref() == Some("button") => {
+                self.dispatch("click", target, TuiEvent::Key(key));
+            }
+            Key::Char(_) | Key::Backspace if self.focused_tag().as_deref() == Some("input") => {
+                let value = {
+                    let mut myself = self.0.borrow_mut();
+                    let node = myself.dom.get(target);
+                    let mut value = node.attribute("value").unwrap_or_default().to_string();
+                    match key {
+                        Key::Char(c) => value.push(c),
+                        _ => {
+                            value.pop();
+                        }
+                    }
+                    myself.dom.set_node_attribute(target, "value", &value);
+                    value
+                };
+                self.dispatch("input", target, TuiEvent::Input { value });
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a click at a cell of the last rendered buffer.
+    pub fn click(&self, x: usize, y: usize) {
+        let target = {
+            let myself = self.0.borrow();
+            // later boxes are nested inside earlier ones
+            myself
+                .backend
+                .boxes
+                .iter()
+                .rev()
+                .find(|(_, rect)| rect.contains(x, y))
+                .map(|(node, _)| *node)
+        };
+        let Some(target) = target else {
+            return;
+        };
+        if is_focusable(&self.dom(), target) {
+            self.set_focus(Some(target));
+        }
+        self.dispatch("click", target, TuiEvent::Mouse { x, y });
+    }
+
+    fn focused_tag(&self) -> Option<String> {
+        let focused = self.focused()?;
+        self.dom().get(focused).tag().map(str::to_string)
+    }
+
+    fn focus_next(&self) {
+        let next = {
+            let dom = self.dom();
+            let mut focusable = Vec::new();
+            collect_focusable(&dom, dom.root(), &mut focusable);
+            let current = self
+                .focused()
+                .and_then(|focused| focusable.iter().position(|node| *node == focused));
+            match current {
+                Some(idx) => focusable.get(idx + 1).or(focusable.first()).copied(),
+                None => focusable.first().copied(),
+            }
+        };
+        self.set_focus(next);
+    }
+
+    fn set_focus(&self, node: Option<NodeId>) {
+        let old = std::mem::replace(&mut self.0.borrow_mut().backend.focused, node);
+        if old == node {
+            return;
+        }
+        if let Some(old) = old {
+            self.dispatch("blur", old, TuiEvent::Focus);
+        }
+        if let Some(node) = node {
+            self.dispatch("focus", node, TuiEvent::Focus);
+        }
+    }
+
+    fn dispatch(&self, name: &str, target: NodeId, event: TuiEvent) {
+        // clone the handlers out so they can use the renderer, and remove their own node, while they run
+        let handlers: Vec<Handler> = {
+            let myself = self.0.borrow();
+            let mut handlers = Vec::new();
+            let mut node = Some(target);
+            while let Some(current) = node {
+                handlers.extend(
+                    myself
+                        .backend
+                        .listeners
+                        .iter()
+                        .filter(|(listener_node, listener_name, bubbles, _)| {
+                            *listener_node == current
+                                && *listener_name == name
+                                && (*bubbles || current == target)
+                        })
+                        .map(|(_, _, _, handler_id)| *handler_id),
+                );
+                node = myself.dom.get(current).parent();
+            }
+            let event_handlers = myself.backend.event_handlers.event_handlers.borrow();
+            handlers
+                .into_iter()
+                .map(|handler_id| {
+                    event_handlers
+                        .get(handler_id)
+                        .expect("handler not found")
+                        .clone()
+                })
+                .collect()
+        };
+
+        for handler in handlers {
+            (handler.borrow_mut())(event.clone());
+        }
+    }
+}
+
+fn is_focusable(dom: &Dom, node: NodeId) -> bool {
+    matches!(dom.get(node).tag(), Some("button" | "input"))
+}
+
+fn collect_focusable(dom: &Dom, node: NodeId, focusable: &mut Vec<NodeId>) {
+    for child in dom.get(node).children() {
+        if is_focusable(dom, *child) {
+            focusable.push(*child);
+        }
+        collect_focusable(dom, *child, focusable);
+    }
+}
+
+struct Layout<'a> {
+    dom: &'a Dom,
+    buffer: &'a mut CellBuffer,
+    boxes: Vec<(NodeId, Rect)>,
+    x: usize,
+    y: usize,
+}
+
+impl Layout<'_> {
+    fn new_line(&mut self, left: usize) {
+        self.x = left;
+        self.y += 1;
+    }
+
+    fn write(&mut self, text: &str, left: usize, width: usize) {
+        for ch in text.chars() {
+            if ch == '\n' || self.x >= left + width {
+                self.new_line(left);
+                if ch == '\n' {
+                    continue;
+                }
+            }
+            self.buffer.put(self.x, self.y, ch);
+            self.x += 1;
+        }
+    }
+
+    fn node(&mut self, node: NodeId, left: usize, width: usize) {
+        let (x, y) = (self.x, self.y);
+        let idx = self.boxes.len();
+        self.boxes.push((
+            node,
+            Rect {
+                x,
+                y,
+                width: 0,
+                height: 0,
+            },
+        ));
+
+        match self.dom.get(node).kind() {
+            NodeKind::Text(text) => self.write(text, left, width),
+            NodeKind::Comment(_) => {}
+            NodeKind::Element { tag, .. } => match tag.as_str() {
+                "button" => {
+                    let label = format!("[{}]", self.dom.text_content(node));
+                    self.write(&label, left, width);
+                }
+                "input" => {
+                    let element = self.dom.get(node);
+                    let size = element
+                        .attribute("size")
+                        .and_then(|size| size.parse().ok())
+                        .unwrap_or(DEFAULT_INPUT_SIZE);
+                    let value: String = element
+                        .attribute("value")
+                        .unwrap_or_default()
+                        .chars()
+                        .chain(std::iter::repeat(' '))
+                        .take(size)
+                        .collect();
+                    self.write(&format!("[{value}]"), left, width);
+                }
+                tag if INLINE_ELEMENTS.contains(&tag) => {
+                    for child in self.dom.get(node).children() {
+                        self.node(*child, left, width);
+                    }
+                }
+                _ => {
+                    if self.x != left {
+                        self.new_line(left);
+                    }
+                    for child in self.dom.get(node).children() {
+                        self.node(*child, left, width);
+                    }
+                    if self.x != left {
+                        self.new_line(left);
+                    }
+                    self.boxes[idx].1 = Rect {
+                        x: left,
+                        y,
+                        width,
+                        height: self.y - y,
+                    };
+                    return;
+                }
+            },
+        }
+
+        // inline content covers the lines it was written on
+        self.boxes[idx].1 = if self.y == y {
+            Rect {
+                x,
+                y,
+                width: self.x - x,
+                height: 1,
+            }
+        } else {
+            Rect {
+                x: left,
+                y,
+                width,
+                height: self.y - y + 1,
+            }
+        };
+    }
+}
+
+impl DomBackend for TuiBackend {
+    type Event = TuiEvent;
+
+    fn add_listener<E: EventDescription<TuiRenderer>>(
+        &mut self,
+        dom: &mut Dom,
+        id: u32,
+        _: E,
+        callback: Box<dyn FnMut(TuiEvent)>,
+    ) {
+        let handler_id =
+            (self.event_handlers.event_handlers.borrow_mut()).id(Rc::new(RefCell::new(callback)));
+        let node = dom.lookup(id).expect("listener added to a missing node");
+        self.listeners.push((node, E::NAME, E::BUBBLES, handler_id));
+    }
+
+    fn hydrated(&mut self, adopted: &HashMap<NodeId, NodeId>) {
+        for (node, _, _, _) in &mut self.listeners {
+            if let Some(server) = adopted.get(node) {
+                *node = *server;
+            }
+        }
+    }
+
+    fn removed(&mut self, dom: &Dom, _: NodeId) {
+        // the focus leaves with the removed node or its ancestor
+        if let Some(mut node) = self.focused {
+            while let Some(parent) = dom.get(node).parent() {
+                node = parent;
+            }
+            if node != dom.root() {
+                self.focused = None;
+            }
+        }
+    }
+
+    fn freed(&mut self, _: &Dom, nodes: &[NodeId]) {
+        let mut handlers = self.event_handlers.event_handlers.borrow_mut();
+        self.listeners.retain(|(node, _, _, handler_id)| {
+            let freed = nodes.contains(node);
+            if freed {
+                handlers.recycle(*handler_id);
+            }
+            !freed
+        });
+        // a click must not reach a later node with the same id before the next render
+        self.boxes.retain(|(node, _)| !nodes.contains(node));
+    }
+}
+
+/// A listener callback, shared so it can keep running after its listener is removed.
+type Handler = Rc<RefCell<Box<dyn FnMut(TuiEvent)>>>;
+
+/// The listener callbacks, indexed by their handler id.
+type EventHandlers = Rc<RefCell<IdSlab<Handler>>>;
+
+#[derive(Default, Clone)]
+struct SharedHandlers {
+    event_handlers: EventHandlers,
+}
+
+#[test]
+fn draws_and_handles_keys() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Counter(cx: Scope) {
+        let num: Rx<i32> = 0;
+
+        rsx! {
+            <div>
+                <span>"count: "</span>
+                <span>"{num}"</span>
+            </div>
+            <button onclick=|_| *num += 1>"+"</button>
+            <button onclick=|_| *num -= 1>"-"</button>
+        }
+    }
+
+    let ui = TuiRenderer::new(20, 3);
+    launch(ui.clone(), Counter {});
+    assert_eq!(ui.render().to_string(), "count: 0\n[+][-]\n\n");
+
+    ui.key(Key::Tab);
+    ui.key(Key::Enter);
+    ui.key(Key::Enter);
+    let buffer = ui.render();
+    assert_eq!(buffer.line(0).unwrap(), "count: 2");
+    assert!(buffer.get(0, 1).unwrap().inverted);
+
+    ui.click(4, 1);
+    assert_eq!(ui.render().line(0).unwrap(), "count: 1");
+}
+
+#[test]
+fn clicks_the_node_under_the_pointer() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Counter(cx: Scope) {
+        let num: Rx<i32> = 0;
+        let rows: Rx<i32> = 0;
+
+        rsx! {
+            <div onclick=|_| *rows += 1>
+                <span>"count: "</span>
+                <span>"{num} {rows}"</span>
+            </div>
+            <button onclick=|_| *num += 1>"+"</button>
+            <button onclick=|_| *num -= 1>"-"</button>
+        }
+    }
+
+    let ui = TuiRenderer::new(20, 3);
+    launch(ui.clone(), Counter {});
+    let buttons = ui.dom().find_all("button");
+    ui.render();
+
+    ui.click(1, 1);
+    ui.click(2, 1);
+    ui.click(3, 1);
+    assert_eq!(ui.focused(), Some(buttons[1]));
+    // the click on the text bubbles to the line it is in
+    ui.click(8, 0);
+    // nothing is drawn there
+    ui.click(12, 1);
+    let buffer = ui.render();
+    assert_eq!(buffer.line(0).unwrap(), "count: 1 1");
+    assert_eq!(ui.focused(), Some(buttons[1]));
+    assert!(buffer.get(3, 1).unwrap().inverted);
+    assert!(!buffer.get(0, 1).unwrap().inverted);
+    assert_eq!(buffer.line(3), None);
+}
+
+#[test]
+fn wraps_focus_and_drops_it_with_the_node() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Dialog(cx: Scope) {
+        let open: Rx<bool> = true;
+
+        rsx! {
+            <input />
+            <if {*open}>
+                <button onclick=|_| *open = false>"close"</button>
+            </if>
+        }
+    }
+
+    let ui = TuiRenderer::new(20, 3);
+    launch(ui.clone(), Dialog {});
+    let input = ui.dom().find_all("input")[0];
+    let button = ui.dom().find_all("button")[0];
+
+    ui.key(Key::Tab);
+    ui.key(Key::Tab);
+    assert_eq!(ui.focused(), Some(button));
+    ui.key(Key::Tab);
+    assert_eq!(ui.focused(), Some(input));
+
+    ui.key(Key::Tab);
+    ui.key(Key::Tab);
+    assert_eq!(ui.focused(), Some(input));
+    ui.key(Key::Tab);
+    ui.key(Key::Enter);
+    // the button removed itself, so nothing is focused until the next tab
+    assert_eq!(ui.focused(), None);
+    assert_eq!(ui.render().to_string(), "[          ]\n\n\n");
+    ui.key(Key::Enter);
+    ui.key(Key::Tab);
+    assert_eq!(ui.focused(), Some(input));
+}
+
+#[test]
+fn edits_the_focused_input() {
+    use crate::prelude::*;
+
+    #[component]
+    fn Form(cx: Scope) {
+        let edits: Rx<i32> = 0;
+
+        rsx! {
+            <input size="6" oninput=|_| *edits += 1 />
+            <p>"edits: {edits}"</p>
+        }
+    }
+
+    let ui = TuiRenderer::new(20, 2);
+    launch(ui.clone(), Form {});
+    let input = ui.dom().find_all("input")[0];
+    // keys do nothing to the input before it is focused
+    ui.key(Key::Char('x'));
+    ui.key(Key::Tab);
+    for key in [
+        Key::Char('a'),
+        Key::Char('b'),
+        Key::Char('c'),
+        Key::Backspace,
+    ] {
+        ui.key(key);
+    }
+    assert_eq!(ui.render().to_string(), "[ab    ]\nedits: 4\n");
+
+    ui.key(Key::Backspace);
+    ui.key(Key::Backspace);
+    // backspace in an empty input leaves it empty
+    ui.key(Key::Backspace);
+    assert_eq!(ui.dom().get(input).attribute("value"), Some(""));
+    assert_eq!(ui.render().to_string(), "[      ]\nedits: 7\n");
+}
@@ -1,5 +1,7 @@
 use js_sys::Function;
+use std::any::TypeId;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::atomic::AtomicU64;
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
@@ -7,7 +9,7 @@ use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
 use crate::slab::IdSlab;
 use crate::{
     events::{EventDescription, PlatformEvents, EVENT_COUNT},
-    renderer::Renderer,
+    renderer::{Hydrate, HydrationError, Renderer},
 };
 
 #[derive(Clone)]
@@ -16,6 +18,8 @@ pub struct WebRenderer(Rc<RefCell<WebRendererInner>>);
 pub struct WebRendererInner {
     channel: Channel,
     ids: IdSlab<()>,
+    templates: HashMap<TypeId, Vec<u32>>,
+    hydrating: bool,
     queued_listeners: Vec<(u32, &'static str, Box<dyn FnMut(web_sys::Event)>)>,
     event_handlers: SharedListeners,
 }
@@ -60,12 +64,42 @@ impl Default for WebRenderer {
         Self(Rc::new(RefCell::new(WebRendererInner {
             channel: Channel::default(),
             ids,
+            templates: HashMap::new(),
+            hydrating: false,
             queued_listeners: Vec::new(),
             event_handlers: SharedListeners::default(),
         })))
     }
 }
 
+impl Hydrate for WebRenderer {
+    fn start_hydration(&mut self) {
+        let mut myself = self.0.borrow_mut();
+        myself.hydrating = true;
+        myself.channel.hydrate_start();
+    }
+
+    fn finish_hydration(&mut self, roots: &[u32]) -> Result<(), HydrationError> {
+        let mut myself = self.0.borrow_mut();
+        myself.hydrating = false;
+        for root in roots {
+            myself.channel.hydrate_root(*root);
+        }
+        myself.channel.hydrate_end();
+        myself.channel.flush();
+        match hydration_error() {
+            Some(path) => {
+                // the claims the listeners were added to never got a node
+                myself.queued_listeners.clear();
+                Err(HydrationError {
+                    path: path.into_iter().map(|idx| idx as usize).collect(),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
 impl Renderer<WebRenderer> for WebRenderer {
     fn node(&mut self) -> u32 {
         let mut myself = self.0.borrow_mut();
@@ -83,6 +117,22 @@ impl Renderer<WebRenderer> for WebRenderer {
         myself.channel.set_attribute(id, name, value);
     }
 
+    fn set_attribute_ns(
+        &mut self,
+        id: u32,
+        name: &'static str,
+        namespace: &'static str,
+        value: &str,
+    ) {
+        let mut myself = self.0.borrow_mut();
+        myself.channel.set_attribute_ns(id, name, namespace, value);
+    }
+
+    fn remove_attribute(&mut self, id: u32, name: &'static str) {
+        let mut myself = self.0.borrow_mut();
+        myself.channel.remove_attribute(id, name);
+    }
+
     fn set_style(&mut self, id: u32, name: &'static str, value: &str) {
         let mut myself = self.0.borrow_mut();
         myself.channel.set_style(id, name, value);
@@ -93,11 +143,21 @@ impl Renderer<WebRenderer> for WebRenderer {
         myself.channel.create_element(id, tag);
     }
 
+    fn create_element_ns(&mut self, id: u32, tag: &'static str, namespace: &'static str) {
+        let mut myself = self.0.borrow_mut();
+        myself.channel.create_element_ns(id, tag, namespace);
+    }
+
     fn create_text(&mut self, id: u32, text: &str) {
         let mut myself = self.0.borrow_mut();
         myself.channel.create_text(id, text);
     }
 
+    fn create_comment(&mut self, id: u32, text: &str) {
+        let mut myself = self.0.borrow_mut();
+        myself.channel.create_comment(id, text);
+    }
+
     fn set_text(&mut self, id: u32, text: &str) {
         let mut myself = self.0.borrow_mut();
         myself.channel.set_text(id, text);
@@ -108,9 +168,24 @@ impl Renderer<WebRenderer> for WebRenderer {
         myself.channel.append_child(parent, child);
     }
 
+    fn insert_before(&mut self, anchor: u32, id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.channel.insert_before(anchor, id);
+    }
+
+    fn move_before(&mut self, anchor: u32, id: u32) {
+        let mut myself = self.0.borrow_mut();
+        myself.channel.move_before(anchor, id);
+    }
+
     fn clone_node(&mut self, id: u32, new_id: u32) {
         let mut myself = self.0.borrow_mut();
-        myself.channel.clone(id, new_id);
+        if myself.hydrating {
+            // the node already exists in the page and is found when hydration finishes
+            myself.channel.claim(id, new_id);
+        } else {
+            myself.channel.clone(id, new_id);
+        }
     }
 
     fn copy(&mut self, id: u32, id2: u32) {
@@ -162,6 +237,16 @@ impl Renderer<WebRenderer> for WebRenderer {
         }
     }
 
+    fn get_template(&mut self, key: TypeId) -> Option<Vec<u32>> {
+        let myself = self.0.borrow();
+        myself.templates.get(&key).cloned()
+    }
+
+    fn set_template(&mut self, key: TypeId, roots: Vec<u32>) {
+        let mut myself = self.0.borrow_mut();
+        myself.templates.insert(key, roots);
+    }
+
     fn flush(&mut self) {
         let mut myself = self.0.borrow_mut();
         myself.channel.flush();
@@ -180,9 +265,117 @@ impl Renderer<WebRenderer> for WebRenderer {
 #[sledgehammer_bindgen::bindgen]
 mod js {
     const JS: &str = r#"const nodes = [document.getElementById("main")];
+    let hydrate_roots = null;
+    let hydrate_error = null;
+    function hydrate(parent){
+        let node = parent.firstChild;
+        while(node){
+            const next = node.nextSibling;
+            if(node.nodeType === Node.COMMENT_NODE && node.data === "t"){
+                if(next && next.nodeType === Node.TEXT_NODE){
+                    node.remove();
+                }else{
+                    node.replaceWith(document.createTextNode(""));
+                }
+            }else if(node.nodeType === Node.ELEMENT_NODE){
+                hydrate(node);
+            }
+            node = next;
+        }
+    }
+    // stands in for a server rendered node while hydrating, it records what is done to it like a node cloned from `template` would
+    class Claim{
+        constructor(template, parent, idx){
+            this.template = template;
+            this.parent = parent;
+            this.idx = idx;
+            this.children = [];
+            this.inserted = [];
+            this.listeners = [];
+            this.style = {};
+            this.node = null;
+        }
+        child(idx){
+            const template = this.template.childNodes[idx];
+            return template ? (this.children[idx] ??= new Claim(template, this, idx)) : null;
+        }
+        get firstChild(){
+            return this.child(0);
+        }
+        get nextSibling(){
+            return this.parent ? this.parent.child(this.idx + 1) : null;
+        }
+        before(node){
+            this.inserted.push(node);
+        }
+        setAttribute(name, value){
+            // the server rendered every other attribute
+            if(name.startsWith("data-event-")){
+                this.listeners.push([name, value]);
+            }
+        }
+        setAttributeNS(){}
+        removeAttribute(){}
+    }
+    function path(node){
+        const path = [];
+        while(node !== nodes[0]){
+            path.unshift([...node.parentNode.childNodes].indexOf(node));
+            node = node.parentNode;
+        }
+        return path;
+    }
+    // match the claims and nodes in children, with the nodes inserted in front of each claim, against the children of server
+    function match_children(children, server, matched){
+        let next = server.firstChild;
+        const match_next = (client, record) => {
+            if(!next){
+                hydrate_error = [...path(server), server.childNodes.length];
+                return false;
+            }
+            const matches = match_node(client, next, record, matched);
+            next = next.nextSibling;
+            return matches;
+        };
+        for(const [child, record] of children){
+            if(child instanceof Claim){
+                for(const inserted of child.inserted){
+                    if(!match_next(inserted, true)){
+                        return false;
+                    }
+                }
+            }
+            if(!match_next(child, record)){
+                return false;
+            }
+        }
+        if(next){
+            hydrate_error = path(next);
+            return false;
+        }
+        return true;
+    }
+    function match_node(client, server, record, matched){
+        const template = client instanceof Claim ? client.template : client;
+        if(template.nodeType !== server.nodeType || template.nodeName !== server.nodeName){
+            hydrate_error = path(server);
+            return false;
+        }
+        if(record){
+            matched.push([client, server]);
+        }
+        // the static nodes of a template have no claim and are only checked
+        const children = client instanceof Claim
+            ? [...template.childNodes].map((node, idx) => client.children[idx] ? [client.children[idx], true] : [node, false])
+            : [...client.childNodes].map((node) => [node, record]);
+        return match_children(children, server, matched);
+    }
     export function get_node(id){
         return nodes[id];
     }
+    export function hydration_error(){
+        return hydrate_error && new Uint32Array(hydrate_error);
+    }
     export function get_handler_id(id, event_id){
         return nodes[id].getAttribute("data"+event_id);
     }"#;
@@ -192,6 +385,8 @@ mod js {
         fn get_node(id: u32) -> web_sys::Node;
         #[wasm_bindgen]
         fn get_handler_id(id: u32, event_id: usize) -> Option<u32>;
+        #[wasm_bindgen]
+        fn hydration_error() -> Option<Vec<u32>>;
     }
 
     fn create_element(id: u32, name: &'static str<u8>) {
@@ -206,6 +401,10 @@ mod js {
         "nodes[$id$]=document.createTextNode($text$);"
     }
 
+    fn create_comment(id: u32, text: &str) {
+        "nodes[$id$]=document.createComment($text$);"
+    }
+
     fn set_style(id: u32, name: &'static str<u8>, val: &str) {
         "nodes[$id$].style[$name$]=$val$;"
     }
@@ -214,6 +413,10 @@ mod js {
         "nodes[$id$].setAttribute($name$,$val$);"
     }
 
+    fn set_attribute_ns(id: u32, name: &'static str<u8>, ns: &'static str<u8>, val: &str) {
+        "nodes[$id$].setAttributeNS($ns$,$name$,$val$);"
+    }
+
     fn remove_attribute(id: u32, name: &'static str<u8>) {
         "nodes[$id$].removeAttribute($name$);"
     }
@@ -222,6 +425,15 @@ mod js {
         "nodes[$id$].appendChild(nodes[$id2$]);"
     }
 
+    fn insert_before(id: u32, id2: u32) {
+        "nodes[$id$].before(nodes[$id2$]);"
+    }
+
+    fn move_before(id: u32, id2: u32) {
+        // moveBefore keeps the state of the moved node where it is supported
+        "{const a=nodes[$id$],p=a.parentNode;if(p.moveBefore){p.moveBefore(nodes[$id2$],a);}else{a.before(nodes[$id2$]);}}"
+    }
+
     fn set_text(id: u32, text: &str) {
         "nodes[$id$].textContent=$text$;"
     }
@@ -250,6 +462,22 @@ mod js {
         "nodes[$id2$]=nodes[$id$];"
     }
 
+    fn claim(id: u32, id2: u32) {
+        "nodes[$id2$]=new Claim(nodes[$id$],null,0);"
+    }
+
+    fn hydrate_start() {
+        r#"for(const node of [...nodes[0].childNodes]){if(node.nodeType!==Node.ELEMENT_NODE&&!node.textContent.trim()&&!(node.nodeType===Node.COMMENT_NODE&&node.data==="t")){node.remove();}}hydrate(nodes[0]);hydrate_roots=[];hydrate_error=null;"#
+    }
+
+    fn hydrate_root(id: u32) {
+        "hydrate_roots.push(nodes[$id$]);"
+    }
+
+    fn hydrate_end() {
+        "{const matched=[];if(match_children(hydrate_roots.map((root)=>[root,true]),nodes[0],matched)){const adopted=new Map();for(const [client,server] of matched){if(client instanceof Claim){client.node=server;for(const [name,value] of client.listeners){server.setAttribute(name,value);}}else{adopted.set(client,server);if(client.nodeType===Node.ELEMENT_NODE){for(const attr of client.attributes){if(attr.name.startsWith(\"data-event-\")){server.setAttribute(attr.name,attr.value);}}}}}for(let i=1;i<nodes.length;i++){const node=nodes[i];if(node instanceof Claim){nodes[i]=node.node;}else if(adopted.has(node)){nodes[i]=adopted.get(node);}}}hydrate_roots=null;}"
+    }
+
     fn add_listener(id: u32, event_id: u16, handler_id: u16) {
         r#"nodes[$id$].setAttribute("data-event-"+($event_id$), $handler_id$);"#
     }